@@ -0,0 +1,210 @@
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use anyhow::Context;
+use bevy::prelude::*;
+use bevy::tasks::{IoTaskPool, Task, block_on};
+use serde::{Deserialize, Serialize};
+
+use crate::editor::EditorState;
+use crate::io::save_map;
+use crate::types::TileMap;
+
+/// Directory (relative to the working directory) that holds autosaves.
+pub const RECOVERY_DIR: &str = "recovery";
+const AUTOSAVE_INTERVAL: Duration = Duration::from_secs(60);
+/// Number of rotating backups kept per map. Slot 0 is always the newest.
+const BACKUP_SLOTS: usize = 5;
+
+pub struct AutosavePlugin;
+
+impl Plugin for AutosavePlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<AutosaveState>()
+            .add_systems(Startup, find_recovery_candidate)
+            .add_systems(Update, run_autosave);
+    }
+}
+
+/// An autosave that is newer than the map it was taken from.
+pub struct RecoveryCandidate {
+    pub key: String,
+    pub source: Option<PathBuf>,
+    pub backup_path: PathBuf,
+    pub saved_at: SystemTime,
+}
+
+#[derive(Resource)]
+pub struct AutosaveState {
+    timer: Timer,
+    task: Option<Task<anyhow::Result<()>>>,
+    /// `EditorState::map_generation` of the last map written, so unchanged maps are not
+    /// saved again.
+    saved_generation: Option<u64>,
+    /// Set at startup when a recovery file should be offered to the user.
+    pub recovery: Option<RecoveryCandidate>,
+}
+
+impl Default for AutosaveState {
+    fn default() -> Self {
+        Self {
+            timer: Timer::new(AUTOSAVE_INTERVAL, TimerMode::Repeating),
+            task: None,
+            saved_generation: None,
+            recovery: None,
+        }
+    }
+}
+
+/// Sidecar written next to the backups of each map.
+#[derive(Serialize, Deserialize)]
+struct RecoveryManifest {
+    source: Option<PathBuf>,
+    /// Seconds since the Unix epoch.
+    saved_at: u64,
+}
+
+fn find_recovery_candidate(mut autosave: ResMut<AutosaveState>, state: Res<EditorState>) {
+    autosave.saved_generation = Some(state.map_generation);
+    autosave.recovery = newest_recovery(Path::new(RECOVERY_DIR));
+}
+
+fn run_autosave(time: Res<Time>, state: Res<EditorState>, mut autosave: ResMut<AutosaveState>) {
+    if let Some(task) = autosave.task.as_mut()
+        && task.is_finished()
+        && let Err(err) = block_on(autosave.task.take().unwrap())
+    {
+        warn!("Autosave failed: {err:#}");
+    }
+
+    if !autosave.timer.tick(time.delta()).just_finished() || autosave.task.is_some() {
+        return;
+    }
+
+    // Don't overwrite the backups before the user has decided what to do with them.
+    if autosave.recovery.is_some() {
+        return;
+    }
+
+    // Maps that match their file on disk need no backup.
+    if !state.map_modified || autosave.saved_generation == Some(state.map_generation) {
+        return;
+    }
+    autosave.saved_generation = Some(state.map_generation);
+
+    let map = state.map.clone();
+    let source = state.current_file_path.clone();
+    autosave.task = Some(
+        IoTaskPool::get()
+            .spawn(async move { write_autosave(Path::new(RECOVERY_DIR), &map, source) }),
+    );
+}
+
+/// Forgets a recovery candidate without deleting its backups.
+pub fn dismiss_recovery(candidate: &RecoveryCandidate) {
+    let manifest = manifest_path(Path::new(RECOVERY_DIR), &candidate.key);
+    if let Err(err) = std::fs::remove_file(&manifest) {
+        warn!("Failed to remove {}: {err}", manifest.display());
+    }
+}
+
+fn write_autosave(dir: &Path, map: &TileMap, source: Option<PathBuf>) -> anyhow::Result<()> {
+    std::fs::create_dir_all(dir)
+        .with_context(|| format!("Failed to create recovery directory {}", dir.display()))?;
+
+    let key = recovery_key(source.as_deref());
+    for slot in (1..BACKUP_SLOTS).rev() {
+        let older = backup_path(dir, &key, slot - 1);
+        if older.exists() {
+            std::fs::rename(&older, backup_path(dir, &key, slot))
+                .with_context(|| format!("Failed to rotate backup {}", older.display()))?;
+        }
+    }
+    save_map(backup_path(dir, &key, 0), map)?;
+
+    let saved_at = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs();
+    let manifest = serde_json::to_vec_pretty(&RecoveryManifest { source, saved_at })?;
+    std::fs::write(manifest_path(dir, &key), manifest)?;
+    Ok(())
+}
+
+fn newest_recovery(dir: &Path) -> Option<RecoveryCandidate> {
+    let mut newest: Option<RecoveryCandidate> = None;
+    for entry in std::fs::read_dir(dir).ok()?.flatten() {
+        let path = entry.path();
+        if path.extension().and_then(|ext| ext.to_str()) != Some("json") {
+            continue;
+        }
+        let Some(key) = path.file_stem().and_then(|stem| stem.to_str()) else {
+            continue;
+        };
+        let Ok(bytes) = std::fs::read(&path) else {
+            continue;
+        };
+        let Ok(manifest) = serde_json::from_slice::<RecoveryManifest>(&bytes) else {
+            continue;
+        };
+
+        let backup = backup_path(dir, key, 0);
+        if !backup.exists() {
+            continue;
+        }
+        let saved_at = UNIX_EPOCH + Duration::from_secs(manifest.saved_at);
+
+        // Only offer autosaves taken after the map was last written to disk.
+        let source_modified = manifest
+            .source
+            .as_ref()
+            .and_then(|source| std::fs::metadata(source).ok())
+            .and_then(|metadata| metadata.modified().ok());
+        if source_modified.is_some_and(|modified| modified >= saved_at) {
+            continue;
+        }
+
+        if newest
+            .as_ref()
+            .is_none_or(|current| current.saved_at < saved_at)
+        {
+            newest = Some(RecoveryCandidate {
+                key: key.to_string(),
+                source: manifest.source,
+                backup_path: backup,
+                saved_at,
+            });
+        }
+    }
+    newest
+}
+
+/// File-name-safe identifier for the backups of one map.
+fn recovery_key(source: Option<&Path>) -> String {
+    let Some(source) = source else {
+        return "untitled".to_string();
+    };
+    let stem: String = source
+        .file_stem()
+        .and_then(|stem| stem.to_str())
+        .unwrap_or("map")
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '_' || c == '-' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    let path_hash = crc32fast::hash(source.to_string_lossy().as_bytes());
+    format!("{stem}-{path_hash:08x}")
+}
+
+fn backup_path(dir: &Path, key: &str, slot: usize) -> PathBuf {
+    dir.join(format!("{key}.{slot}.bin"))
+}
+
+fn manifest_path(dir: &Path, key: &str) -> PathBuf {
+    dir.join(format!("{key}.json"))
+}
@@ -0,0 +1,249 @@
+//! Headless subcommands for build pipelines. They run before any Bevy app is created,
+//! so no window or GPU is needed. Asset paths resolve against `assets/` in the working
+//! directory, as they do in the editor.
+
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result, bail};
+
+use dprmapedit::export;
+use dprmapedit::io::{self, load_map, save_map};
+use dprmapedit::raster::{HeightFormat, RasterExportOptions};
+use dprmapedit::terrain::TopNormals;
+use dprmapedit::texture::registry::TerrainTextureRegistry;
+use dprmapedit::tiled::{self, TiledMapping};
+use dprmapedit::types::{TileKind, TileMap, TileType};
+use dprmapedit::validation::{self, Severity};
+
+const USAGE: &str = "\
+Usage:
+  dprmapedit                              Open the editor
+  dprmapedit validate [--strict] <map>... Check maps; --strict also fails on warnings
+  dprmapedit export <map> <out.tmemapdata> [--name <name>] [--rasters] [--raw-heights]
+                                          [--samples <n>] [--chunks] [--smooth-normals]
+                                          [--optimize] [--lods <0-3>]
+                                          Build a map package with the built-in textures
+  dprmapedit convert <in> <out>           Convert between .bin, .json, .ron and Tiled .tmj
+  dprmapedit info <map>...                Print size and tile statistics
+  dprmapedit help                         Show this message";
+
+/// Runs a subcommand if one was given and returns the process exit code.
+/// Returns `None` when the editor should start instead.
+pub fn run(args: Vec<String>) -> Option<i32> {
+    let (command, rest) = args.split_first()?;
+    let result = match command.as_str() {
+        "validate" => validate(rest),
+        "export" => export_map(rest),
+        "convert" => convert(rest),
+        "info" => info(rest),
+        "help" | "--help" | "-h" => {
+            println!("{USAGE}");
+            Ok(true)
+        }
+        other => {
+            eprintln!("Unknown command `{other}`\n\n{USAGE}");
+            return Some(2);
+        }
+    };
+    match result {
+        Ok(true) => Some(0),
+        Ok(false) => Some(1),
+        Err(err) => {
+            eprintln!("error: {err:#}");
+            Some(1)
+        }
+    }
+}
+
+/// Loads any map the CLI can read; `.tmj` files go through the Tiled importer.
+fn load_any(path: &Path) -> Result<TileMap> {
+    if is_tiled(path) {
+        let mapping = TiledMapping::load_or_default(Path::new(tiled::MAPPING_FILE))?;
+        tiled::import_tiled(path, &mapping)
+    } else {
+        load_map(path)
+    }
+}
+
+fn is_tiled(path: &Path) -> bool {
+    path.extension()
+        .and_then(|ext| ext.to_str())
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("tmj"))
+}
+
+fn validate(args: &[String]) -> Result<bool> {
+    let strict = args.iter().any(|arg| arg == "--strict");
+    let paths: Vec<&String> = args.iter().filter(|arg| *arg != "--strict").collect();
+    if paths.is_empty() {
+        bail!("validate needs at least one map\n\n{USAGE}");
+    }
+
+    let mut passed = true;
+    for path in paths {
+        let map = load_any(Path::new(path))?;
+        let diagnostics = validation::validate_map(&map);
+        for diagnostic in &diagnostics {
+            let label = match diagnostic.severity {
+                Severity::Error => "error",
+                Severity::Warning => "warning",
+            };
+            println!(
+                "{path}:{}:{}: {label}: {}",
+                diagnostic.x,
+                diagnostic.y,
+                diagnostic.message()
+            );
+        }
+        let failed = diagnostics
+            .iter()
+            .any(|diagnostic| strict || diagnostic.severity == Severity::Error);
+        if failed {
+            passed = false;
+        } else {
+            println!("{path}: ok ({} warnings)", diagnostics.len());
+        }
+    }
+    Ok(passed)
+}
+
+fn export_map(args: &[String]) -> Result<bool> {
+    let mut positional = Vec::new();
+    let mut name = None;
+    let mut rasters = false;
+    let mut chunked_mesh = false;
+    let mut top_normals = TopNormals::Flat;
+    let mut optimize_mesh = false;
+    let mut mesh_lods = 0;
+    let mut height_format = HeightFormat::Png16;
+    let mut samples = RasterExportOptions::default().samples_per_tile;
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        match arg.as_str() {
+            "--name" => name = Some(iter.next().context("--name needs a value")?.clone()),
+            "--rasters" => rasters = true,
+            "--chunks" => chunked_mesh = true,
+            "--smooth-normals" => top_normals = TopNormals::Smooth,
+            "--optimize" => optimize_mesh = true,
+            "--lods" => {
+                mesh_lods = iter
+                    .next()
+                    .context("--lods needs a value")?
+                    .parse()
+                    .context("--lods must be a number")?;
+                if mesh_lods > export::MAX_MESH_LODS {
+                    bail!("--lods must be at most {}", export::MAX_MESH_LODS);
+                }
+            }
+            "--raw-heights" => height_format = HeightFormat::Raw16,
+            "--samples" => {
+                samples = iter
+                    .next()
+                    .context("--samples needs a value")?
+                    .parse()
+                    .context("--samples must be a positive number")?;
+            }
+            _ => positional.push(PathBuf::from(arg)),
+        }
+    }
+    let [map_path, output] = positional.as_slice() else {
+        bail!("export needs a map and an output path\n\n{USAGE}");
+    };
+
+    let map = load_any(map_path)?;
+    let name = name.unwrap_or_else(|| {
+        map_path
+            .file_stem()
+            .and_then(|stem| stem.to_str())
+            .unwrap_or("map")
+            .to_string()
+    });
+    let registry = TerrainTextureRegistry::builtin_paths();
+    let (descriptors, wall_descriptor) = export::collect_texture_descriptors(&map, &registry)?;
+    let splat_png = export::build_map_splatmap_png(&map)?;
+    let options = export::PackageExportOptions {
+        rasters: rasters.then_some(RasterExportOptions {
+            samples_per_tile: samples,
+            height_format,
+        }),
+        chunked_mesh,
+        top_normals,
+        optimize_mesh,
+        mesh_lods,
+    };
+
+    export::export_package(
+        output,
+        map,
+        name,
+        descriptors,
+        wall_descriptor,
+        splat_png,
+        options,
+    )?;
+    println!("Exported {} to {}", map_path.display(), output.display());
+    Ok(true)
+}
+
+fn convert(args: &[String]) -> Result<bool> {
+    let [input, output] = args else {
+        bail!("convert needs an input and an output path\n\n{USAGE}");
+    };
+    let (input, output) = (Path::new(input), Path::new(output));
+    let map = load_any(input)?;
+    if is_tiled(output) {
+        let mapping = TiledMapping::load_or_default(Path::new(tiled::MAPPING_FILE))?;
+        tiled::export_tiled(output, &map, &mapping)?;
+    } else {
+        save_map(output, &map)?;
+    }
+    println!("Converted {} to {}", input.display(), output.display());
+    Ok(true)
+}
+
+fn info(args: &[String]) -> Result<bool> {
+    if args.is_empty() {
+        bail!("info needs at least one map\n\n{USAGE}");
+    }
+    for path in args {
+        let path = Path::new(path);
+        let map = load_any(path).with_context(|| format!("Failed to read {}", path.display()))?;
+        let format = if is_tiled(path) {
+            "Tiled JSON".to_string()
+        } else {
+            format!("{:?}", io::MapFormat::from_path(path))
+        };
+
+        println!("{}", path.display());
+        println!("  format:          {format}");
+        println!(
+            "  size:            {} x {} ({} tiles)",
+            map.width,
+            map.height,
+            map.tiles.len()
+        );
+        for tile_type in TileType::ALL {
+            let count = map
+                .tiles
+                .iter()
+                .filter(|tile| tile.tile_type == tile_type)
+                .count();
+            if count > 0 {
+                println!("  {:<16} {count}", format!("{}:", tile_type.identifier()));
+            }
+        }
+        let ramps = map
+            .tiles
+            .iter()
+            .filter(|tile| tile.kind == TileKind::Ramp)
+            .count();
+        println!("  ramps:           {ramps}");
+        if let (Some(low), Some(high)) = (
+            map.tiles.iter().map(|tile| tile.elevation).min(),
+            map.tiles.iter().map(|tile| tile.elevation).max(),
+        ) {
+            println!("  elevation:       {low}..={high}");
+        }
+        println!("  walk overrides:  {}", map.walk_overrides.len());
+    }
+    Ok(true)
+}
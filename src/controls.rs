@@ -2,10 +2,14 @@ use bevy::input::mouse::MouseWheel;
 use bevy::prelude::*;
 use bevy_egui::EguiContexts;
 
+use crate::editor::EditorState;
+use crate::terrain;
+use crate::types::TILE_SIZE;
+
 pub struct ControlsPlugin;
 impl Plugin for ControlsPlugin {
     fn build(&self, app: &mut App) {
-        app.add_systems(Update, camera_move);
+        app.add_systems(Update, (camera_move, focus_camera));
     }
 }
 
@@ -58,3 +62,28 @@ fn camera_move(
         }
     }
 }
+
+fn focus_camera(mut q_cam: Query<&mut Transform, With<Camera3d>>, mut state: ResMut<EditorState>) {
+    let Some((x, y)) = state.focus_request.take() else {
+        return;
+    };
+    if x >= state.map.width || y >= state.map.height {
+        return;
+    }
+
+    let mut t = q_cam.single_mut();
+    let heights = terrain::tile_corner_heights(&state.map, x, y);
+    let target = Vec3::new(
+        (x as f32 + 0.5) * TILE_SIZE,
+        heights.iter().sum::<f32>() / 4.0,
+        (y as f32 + 0.5) * TILE_SIZE,
+    );
+
+    // Slide along the view direction so the camera keeps its height and angle.
+    let forward: Vec3 = t.forward().into();
+    if forward.y.abs() < f32::EPSILON {
+        return;
+    }
+    let distance = (t.translation.y - target.y) / -forward.y;
+    t.translation = target - forward * distance;
+}
@@ -1,8 +1,14 @@
+use crate::export::ImportedPackage;
+use crate::generate::NewMapSettings;
+use crate::heightmap::HeightmapImportSettings;
+use crate::pathing::{self, PathResult};
+use crate::query::{TerrainFace, TerrainQuery};
+use crate::raster::RasterExportOptions;
 use crate::terrain;
 use crate::texture::material::TerrainMaterial;
-use crate::texture::registry::TerrainTextureRegistry;
+use crate::texture::registry::{BUILTIN_TEXTURES, BUILTIN_WALL_TEXTURE, TerrainTextureRegistry};
 use crate::types::*;
-use bevy::pbr::MaterialMeshBundle;
+use crate::validation::{self, Diagnostic};
 use bevy::prelude::*;
 use bevy::tasks::Task;
 use bevy_egui::EguiContexts;
@@ -26,13 +32,17 @@ impl Plugin for EditorPlugin {
                     update_hover,
                     paint_tiles,
                     rotate_ramps,
+                    paint_walkability,
+                    pick_path_points,
                     draw_hover_highlight,
+                    draw_path_preview,
                 )
                     .before(terrain::TerrainMeshSet::Rebuild),
             )
             .add_systems(
                 Update,
-                rebuild_terrain_mesh.in_set(terrain::TerrainMeshSet::Rebuild),
+                (refresh_path_preview, refresh_diagnostics)
+                    .in_set(terrain::TerrainMeshSet::Rebuild),
             )
             .add_systems(
                 Update,
@@ -45,6 +55,41 @@ impl Plugin for EditorPlugin {
 pub enum EditorTool {
     Paint,
     RotateRamp,
+    Walkability,
+    PathPreview,
+}
+
+/// An action that would replace the current map and needs confirmation if it has unsaved edits.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum PendingAction {
+    Load,
+    OpenPackage,
+    ImportTiled,
+    New(NewMapSettings),
+    Close,
+}
+
+/// Which image a file picker in the heightmap import dialog is choosing.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum HeightmapImage {
+    Heights,
+    TileTypes,
+}
+
+/// State of the heightmap import dialog while it is open.
+#[derive(Default)]
+pub struct HeightmapImportDialog {
+    pub settings: HeightmapImportSettings,
+    pub heightmap: Option<PathBuf>,
+    pub tile_types: Option<PathBuf>,
+    pub pick_task: Option<(HeightmapImage, Task<Option<PathBuf>>)>,
+}
+
+/// Route found by the path preview tool, cached until the endpoints or map change.
+pub struct PathPreview {
+    pub result: Option<PathResult>,
+    pub polyline: Vec<Vec3>,
+    pub length_world: f32,
 }
 
 #[derive(Resource)]
@@ -53,15 +98,62 @@ pub struct EditorState {
     pub current_kind: TileKind,
     pub current_elev: i8, // -1..3
     pub current_texture: TileType,
+    pub current_walk_override: Option<WalkOverride>,
     pub hover: Option<(u32, u32)>,
+    /// Face of the hovered tile under the cursor; `Side` while over a cliff or ramp wall.
+    pub hover_face: Option<TerrainFace>,
     pub map: TileMap,
+    /// Set whenever the map changes; cleared each frame once meshes have been rebuilt.
     pub map_dirty: bool,
+    /// Tiles changed this frame while `map_dirty` is set; `None` means the whole map.
+    pub dirty_tiles: Option<TileRect>,
+    /// Bumped on every change to the map, unlike `map_dirty` never reset. Lets
+    /// slower consumers such as autosave tell whether the map changed since they looked.
+    pub map_generation: u64,
+    /// Set by edits and cleared when the map is saved or loaded from disk.
+    pub map_modified: bool,
+    /// Action waiting on the Save / Discard / Cancel prompt.
+    pub unsaved_prompt: Option<PendingAction>,
+    /// Action to run once the save dialog opened from the prompt has written the map.
+    pub after_save: Option<PendingAction>,
+    /// Set once closing the window has been confirmed; the app exits on the next update.
+    pub close_confirmed: bool,
     pub show_grid: bool,
+    pub show_walkability: bool,
+    pub path_start: Option<(u32, u32)>,
+    pub path_goal: Option<(u32, u32)>,
+    pub path_preview: Option<PathPreview>,
+    pub show_validation: bool,
+    /// Settings being edited in the New Map dialog; `None` while it is closed.
+    pub new_map_dialog: Option<NewMapSettings>,
+    pub heightmap_dialog: Option<HeightmapImportDialog>,
+    /// Cached validation results; `None` until the panel needs them.
+    pub diagnostics: Option<Vec<Diagnostic>>,
+    pub focused_tile: Option<(u32, u32)>,
+    /// Tile the camera should centre on next frame.
+    pub focus_request: Option<(u32, u32)>,
     pub current_file_path: Option<PathBuf>,
     pub save_dialog_task: Option<Task<Option<PathBuf>>>,
     pub load_dialog_task: Option<Task<Option<PathBuf>>>,
+    pub package_dialog_task: Option<Task<Option<PathBuf>>>,
+    /// Extracts the picked package and its textures off the main thread.
+    pub package_import_task: Option<Task<anyhow::Result<(PathBuf, ImportedPackage)>>>,
+    pub tiled_import_task: Option<Task<Option<PathBuf>>>,
+    pub tiled_export_task: Option<Task<Option<PathBuf>>>,
     pub export_dialog_task: Option<Task<Option<PathBuf>>>,
     pub export_task: Option<Task<anyhow::Result<PathBuf>>>,
+    pub raster_dialog_task: Option<Task<Option<PathBuf>>>,
+    pub raster_options: RasterExportOptions,
+    /// Bundle the rasters into `.tmemapdata` packages as well.
+    pub export_rasters_in_package: bool,
+    /// Split the packaged `mesh.glb` into one node per terrain chunk.
+    pub export_chunked_mesh: bool,
+    /// Shading of tile tops in the packaged `mesh.glb`.
+    pub export_top_normals: terrain::TopNormals,
+    /// Merge coplanar faces in the packaged `mesh.glb`.
+    pub export_optimize_mesh: bool,
+    /// Simplified LOD levels added to the packaged `mesh.glb`.
+    pub export_mesh_lods: u32,
     pub last_export_status: Option<ExportStatus>,
 }
 impl Default for EditorState {
@@ -71,35 +163,100 @@ impl Default for EditorState {
             current_kind: TileKind::Floor,
             current_elev: 0,
             current_texture: TileType::default(),
+            current_walk_override: Some(WalkOverride::Blocked),
             hover: None,
+            hover_face: None,
             map: TileMap::new(64, 64),
             map_dirty: true,
+            dirty_tiles: None,
+            map_generation: 0,
+            map_modified: false,
+            unsaved_prompt: None,
+            after_save: None,
+            close_confirmed: false,
             show_grid: true,
+            show_walkability: false,
+            path_start: None,
+            path_goal: None,
+            path_preview: None,
+            show_validation: false,
+            new_map_dialog: None,
+            heightmap_dialog: None,
+            diagnostics: None,
+            focused_tile: None,
+            focus_request: None,
             current_file_path: None,
             save_dialog_task: None,
             load_dialog_task: None,
+            package_dialog_task: None,
+            package_import_task: None,
+            tiled_import_task: None,
+            tiled_export_task: None,
             export_dialog_task: None,
             export_task: None,
+            raster_dialog_task: None,
+            raster_options: RasterExportOptions::default(),
+            export_rasters_in_package: false,
+            export_chunked_mesh: false,
+            export_top_normals: terrain::TopNormals::Flat,
+            export_optimize_mesh: false,
+            export_mesh_lods: 0,
             last_export_status: None,
         }
     }
 }
 
-#[derive(Resource)]
-struct TerrainVisual {
-    layers: std::collections::HashMap<TileType, TerrainLayer>,
-}
+impl EditorState {
+    /// Flags the whole map for rebuilding, e.g. after it was loaded or replaced.
+    pub fn mark_map_dirty(&mut self) {
+        self.map_dirty = true;
+        self.dirty_tiles = None;
+        self.map_generation += 1;
+    }
 
-impl Default for TerrainVisual {
-    fn default() -> Self {
-        Self {
-            layers: std::collections::HashMap::new(),
-        }
+    /// Flags the tiles in `rect` for rebuilding. Only the terrain around them is
+    /// re-meshed, unless the whole map is already dirty this frame.
+    pub fn mark_tiles_dirty(&mut self, rect: TileRect) {
+        self.dirty_tiles = match (self.map_dirty, self.dirty_tiles) {
+            (false, _) => Some(rect),
+            (true, Some(existing)) => Some(existing.union(rect)),
+            (true, None) => None,
+        };
+        self.map_dirty = true;
+        self.map_generation += 1;
+    }
+
+    /// Swaps in `map` and forgets the tile coordinates that pointed into the old one,
+    /// which may be out of bounds now. Every map replacement goes through here.
+    pub fn replace_map(&mut self, map: TileMap) {
+        self.map = map;
+        self.mark_map_dirty();
+        self.hover = None;
+        self.hover_face = None;
+        self.path_start = None;
+        self.path_goal = None;
+        self.path_preview = None;
+        self.diagnostics = None;
+        self.focused_tile = None;
     }
-}
 
-struct TerrainLayer {
-    mesh: Handle<Mesh>,
+    /// Replaces the map with a fresh, unsaved one and drops everything tied to the old map.
+    pub fn start_new_map(&mut self, map: TileMap) {
+        self.focus_request = Some((map.width / 2, map.height / 2));
+        self.replace_map(map);
+        self.map_modified = false;
+        self.current_file_path = None;
+        self.save_dialog_task = None;
+        self.load_dialog_task = None;
+        self.package_dialog_task = None;
+        self.package_import_task = None;
+        self.tiled_import_task = None;
+        self.tiled_export_task = None;
+        self.export_dialog_task = None;
+        self.raster_dialog_task = None;
+        self.unsaved_prompt = None;
+        self.after_save = None;
+    }
 }
 
 #[derive(Default, Reflect, GizmoConfigGroup)]
@@ -112,92 +269,35 @@ fn configure_hover_gizmos(mut configs: ResMut<GizmoConfigStore>) {
 }
 
 fn spawn_editor_assets(
-    mut commands: Commands,
     mut mats: ResMut<Assets<TerrainMaterial>>,
-    mut meshes: ResMut<Assets<Mesh>>,
     asset_server: Res<AssetServer>,
     mut textures: ResMut<TerrainTextureRegistry>,
 ) {
-    let texture_defs = [
-        (
-            TileType::Grass,
-            "Rocky Terrain",
-            "textures/terrain/rocky_terrain_02_diff_1k.png",
-            Some("textures/terrain/rocky_terrain_02_nor_gl_1k_fixed.exr"),
-            Some("textures/terrain/roughness_l8.png"),
-            Some("textures/terrain/rocky_terrain_02_disp_1k.png"),
-        ),
-        (
-            TileType::Dirt,
-            "Worn Soil",
-            "textures/terrain/rocky_terrain_02_diff_1k.png",
-            Some("textures/terrain/rocky_terrain_02_nor_gl_1k_fixed.exr"),
-            Some("textures/terrain/roughness_l8.png"),
-            Some("textures/terrain/rocky_terrain_02_disp_1k.png"),
-        ),
-        (
-            TileType::Sand,
-            "Sandstone",
-            "textures/terrain/rock/aerial_ground_rock_diff_1k.png",
-            Some("textures/terrain/rock/aerial_ground_rock_nor_gl_1k_fixed.exr"),
-            Some("textures/terrain/rock/roughness_in_G.png"),
-            Some("textures/terrain/rock/aerial_ground_rock_disp_1k.png"),
-        ),
-        (
-            TileType::Rock,
-            "Ground Rock",
-            "textures/terrain/rock/aerial_ground_rock_diff_1k.png",
-            Some("textures/terrain/rock/aerial_ground_rock_nor_gl_1k_fixed.exr"),
-            Some("textures/terrain/rock/roughness_in_G.png"),
-            Some("textures/terrain/rock/aerial_ground_rock_disp_1k.png"),
-        ),
-    ];
-
-    for (tile_type, name, base, normal, roughness, dispersion) in texture_defs {
+    for texture in &BUILTIN_TEXTURES {
         textures.load_and_register(
-            tile_type,
-            name,
+            texture.tile_type,
+            texture.name,
             &asset_server,
             &mut mats,
-            base,
-            normal,
-            roughness,
-            dispersion,
+            texture.base_color,
+            texture.normal,
+            texture.roughness,
+            texture.dispersion,
         );
     }
 
+    let wall = &BUILTIN_WALL_TEXTURE;
     textures.load_and_register_wall(
-        "wall",
-        "Cliff Wall",
+        wall.id,
+        wall.name,
         &asset_server,
-        "textures/terrain/rock/aerial_ground_rock_diff_1k.png",
-        Some("textures/terrain/rock/aerial_ground_rock_nor_gl_1k_fixed.exr"),
-        Some("textures/terrain/rock/roughness_in_G.png"),
+        wall.base_color,
+        wall.normal,
+        wall.roughness,
     );
-
-    let mut visual = TerrainVisual::default();
-
-    for entry in textures.iter() {
-        let tile_type = entry.tile_type;
-        let mesh = meshes.add(terrain::empty_mesh());
-        commands.spawn((
-            MaterialMeshBundle {
-                mesh: mesh.clone(),
-                material: entry.material.clone(),
-                transform: Transform::default(),
-                visibility: Visibility::Hidden,
-                ..default()
-            },
-            Name::new(format!("EditorTerrain::{tile_type:?}")),
-        ));
-
-        visual.layers.insert(tile_type, TerrainLayer { mesh });
-    }
-
-    commands.insert_resource(visual);
 }
 
-// Raycast to ground plane at chosen elevation (use current_elev for edit layer)
+/// Finds the tile and face under the cursor, or clears them while egui has the pointer.
 fn update_hover(
     mut state: ResMut<EditorState>,
     windows: Query<&Window>,
@@ -209,47 +309,18 @@ fn update_hover(
 
     if egui.ctx_mut().wants_pointer_input() {
         state.hover = None;
+        state.hover_face = None;
         return;
     }
 
-    let Some(cursor) = win.cursor_position() else {
-        state.hover = None;
-        return;
-    };
-
-    if let Some(ray) = cam.viewport_to_world(cam_xform, cursor) {
-        // --- Step 1: flat projection (y = 0) candidate
-        let guess_hit = ray.origin + ray.direction * ((0.0 - ray.origin.y) / ray.direction.y);
-        let tx = (guess_hit.x / TILE_SIZE).floor() as i32;
-        let ty = (guess_hit.z / TILE_SIZE).floor() as i32;
-
-        if tx >= 0 && ty >= 0 && (tx as u32) < state.map.width && (ty as u32) < state.map.height {
-            // Look up elevation at this flat tile
-            let idx = (ty as u32 * state.map.width + tx as u32) as usize;
-            let elev = state.map.tiles[idx].elevation as f32 * TILE_HEIGHT;
-
-            // --- Step 2: recompute ray-plane hit at elevation
-            let t = (elev - ray.origin.y) / ray.direction.y;
-            if t.is_finite() && t > 0.0 {
-                let hit = ray.origin + ray.direction * t;
-                let x2 = (hit.x / TILE_SIZE).floor() as i32;
-                let y2 = (hit.z / TILE_SIZE).floor() as i32;
-
-                // Prefer elevated if it resolves to the same tile coords
-                if x2 == tx && y2 == ty {
-                    state.hover = Some((x2 as u32, y2 as u32));
-                    return;
-                }
-            }
-
-            // --- Fallback to flat tile
-            state.hover = Some((tx as u32, ty as u32));
-            return;
-        }
-    }
-
-    // --- Nothing hit
-    state.hover = None;
+    // Pick against the terrain geometry itself so ramps and cliff walls resolve
+    // to the tile that is drawn under the cursor.
+    let hit = win
+        .cursor_position()
+        .and_then(|cursor| cam.viewport_to_world(cam_xform, cursor))
+        .and_then(|ray| TerrainQuery::new(&state.map).raycast(ray, f32::INFINITY));
+    state.hover = hit.map(|hit| hit.tile);
+    state.hover_face = hit.map(|hit| hit.face);
 }
 
 fn paint_tiles(
@@ -273,7 +344,12 @@ fn paint_tiles(
             let target_ramp_direction = if kind == TileKind::Ramp {
                 let base = elevation as f32 * TILE_HEIGHT;
                 let candidates = ramp_targets(&state_ref.map, x, y, base);
-                if let Some(existing) = current.ramp_direction {
+                if let Some(TerrainFace::Side(side)) = state_ref.hover_face
+                    && candidates.contains(&side)
+                {
+                    // Painting onto a cliff wall points the ramp down over that wall.
+                    Some(side)
+                } else if let Some(existing) = current.ramp_direction {
                     if candidates.contains(&existing) {
                         Some(existing)
                     } else {
@@ -303,7 +379,8 @@ fn paint_tiles(
                         ramp_direction: target_ramp_direction,
                     },
                 );
-                state_ref.map_dirty = true;
+                state_ref.mark_tiles_dirty(TileRect::tile(x, y));
+                state_ref.map_modified = true;
             }
         }
     }
@@ -339,15 +416,22 @@ fn rotate_ramps(
         return;
     }
 
-    let next_direction = match base_tile.ramp_direction {
-        Some(current) => {
-            if let Some(idx) = candidates.iter().position(|&dir| dir == current) {
-                candidates[(idx + 1) % candidates.len()]
-            } else {
-                candidates[0]
+    let next_direction = if let Some(TerrainFace::Side(side)) = state.hover_face
+        && candidates.contains(&side)
+    {
+        // Clicking one of the ramp's walls points it down over that wall.
+        side
+    } else {
+        match base_tile.ramp_direction {
+            Some(current) => {
+                if let Some(idx) = candidates.iter().position(|&dir| dir == current) {
+                    candidates[(idx + 1) % candidates.len()]
+                } else {
+                    candidates[0]
+                }
             }
+            None => candidates[0],
         }
-        None => candidates[0],
     };
 
     if base_tile.ramp_direction == Some(next_direction) {
@@ -357,7 +441,97 @@ fn rotate_ramps(
     let mut updated = base_tile;
     updated.ramp_direction = Some(next_direction);
     state.map.set(x, y, updated);
-    state.map_dirty = true;
+    state.mark_tiles_dirty(TileRect::tile(x, y));
+    state.map_modified = true;
+}
+
+fn paint_walkability(
+    buttons: Res<ButtonInput<MouseButton>>,
+    mut state: ResMut<EditorState>,
+    mut egui: EguiContexts,
+) {
+    if egui.ctx_mut().wants_pointer_input() {
+        return;
+    }
+    if state.current_tool != EditorTool::Walkability {
+        return;
+    }
+    if !buttons.pressed(MouseButton::Left) {
+        return;
+    }
+
+    let Some((x, y)) = state.hover else {
+        return;
+    };
+
+    let mode = state.current_walk_override;
+    if state.map.set_walk_override(x, y, mode) {
+        state.mark_tiles_dirty(TileRect::tile(x, y));
+        state.map_modified = true;
+    }
+}
+
+fn pick_path_points(
+    buttons: Res<ButtonInput<MouseButton>>,
+    mut state: ResMut<EditorState>,
+    mut egui: EguiContexts,
+) {
+    if egui.ctx_mut().wants_pointer_input() {
+        return;
+    }
+    if state.current_tool != EditorTool::PathPreview {
+        return;
+    }
+    if !buttons.just_pressed(MouseButton::Left) {
+        return;
+    }
+
+    let Some(tile) = state.hover else {
+        return;
+    };
+
+    // First click (or a click after a finished route) picks a new start.
+    if state.path_start.is_none() || state.path_goal.is_some() {
+        state.path_start = Some(tile);
+        state.path_goal = None;
+        state.path_preview = None;
+        return;
+    }
+
+    state.path_goal = Some(tile);
+    state.path_preview = compute_path_preview(&state);
+}
+
+fn refresh_path_preview(mut state: ResMut<EditorState>) {
+    if !state.map_dirty || state.path_goal.is_none() {
+        return;
+    }
+    state.path_preview = compute_path_preview(&state);
+}
+
+fn refresh_diagnostics(mut state: ResMut<EditorState>) {
+    if !state.show_validation {
+        return;
+    }
+    if state.map_dirty || state.diagnostics.is_none() {
+        state.diagnostics = Some(validation::validate_map(&state.map));
+    }
+}
+
+fn compute_path_preview(state: &EditorState) -> Option<PathPreview> {
+    let (start, goal) = (state.path_start?, state.path_goal?);
+    let grid = pathing::build_walk_grid(&state.map);
+    let result = pathing::find_path(&grid, start, goal);
+    let polyline = result
+        .as_ref()
+        .map(|path| pathing::path_polyline(&state.map, &path.cells))
+        .unwrap_or_default();
+    let length_world = pathing::polyline_length(&polyline);
+    Some(PathPreview {
+        result,
+        polyline,
+        length_world,
+    })
 }
 
 fn ramp_targets(map: &TileMap, x: u32, y: u32, base: f32) -> Vec<RampDirection> {
@@ -382,52 +556,124 @@ fn ramp_targets(map: &TileMap, x: u32, y: u32, base: f32) -> Vec<RampDirection>
     results
 }
 
-fn rebuild_terrain_mesh(
-    state: Res<EditorState>,
-    mut meshes: ResMut<Assets<Mesh>>,
-    visual: Res<TerrainVisual>,
-) {
-    if !state.map_dirty {
-        return;
-    }
-
-    let mesh_map = terrain::build_map_meshes(&state.map);
-
-    for (tile_type, layer) in &visual.layers {
-        let mesh = mesh_map
-            .get(tile_type)
-            .cloned()
-            .unwrap_or_else(terrain::empty_mesh);
-
-        if let Some(existing) = meshes.get_mut(&layer.mesh) {
-            *existing = mesh;
-        }
-    }
-}
-
 fn mark_map_clean(mut state: ResMut<EditorState>) {
     if state.map_dirty {
         state.map_dirty = false;
+        state.dirty_tiles = None;
     }
 }
 
 fn draw_hover_highlight(mut gizmos: Gizmos<HoverGizmoGroup>, state: Res<EditorState>) {
     if let Some((x, y)) = state.hover {
-        let heights = terrain::tile_corner_heights(&state.map, x, y);
-        let offset = 0.02;
-        let x0 = x as f32 * TILE_SIZE;
-        let x1 = x0 + TILE_SIZE;
-        let z0 = y as f32 * TILE_SIZE;
-        let z1 = z0 + TILE_SIZE;
         gizmos.linestrip(
-            [
-                Vec3::new(x0, heights[terrain::CORNER_NW] + offset, z0),
-                Vec3::new(x1, heights[terrain::CORNER_NE] + offset, z0),
-                Vec3::new(x1, heights[terrain::CORNER_SE] + offset, z1),
-                Vec3::new(x0, heights[terrain::CORNER_SW] + offset, z1),
-                Vec3::new(x0, heights[terrain::CORNER_NW] + offset, z0),
-            ],
+            tile_outline(&state.map, x, y, 0.02),
             Color::srgb(0.0, 1.0, 0.0),
         );
+        if let Some(TerrainFace::Side(side)) = state.hover_face {
+            gizmos.linestrip(
+                wall_outline(&state.map, x, y, side, 0.02),
+                Color::srgb(0.0, 1.0, 0.0),
+            );
+        }
+    }
+
+    if let Some((x, y)) = state.focused_tile
+        && x < state.map.width
+        && y < state.map.height
+    {
+        gizmos.linestrip(
+            tile_outline(&state.map, x, y, 0.04),
+            Color::srgb(1.0, 0.55, 0.0),
+        );
+    }
+}
+
+fn tile_outline(map: &TileMap, x: u32, y: u32, offset: f32) -> [Vec3; 5] {
+    let heights = terrain::tile_corner_heights(map, x, y);
+    let x0 = x as f32 * TILE_SIZE;
+    let x1 = x0 + TILE_SIZE;
+    let z0 = y as f32 * TILE_SIZE;
+    let z1 = z0 + TILE_SIZE;
+    [
+        Vec3::new(x0, heights[terrain::CORNER_NW] + offset, z0),
+        Vec3::new(x1, heights[terrain::CORNER_NE] + offset, z0),
+        Vec3::new(x1, heights[terrain::CORNER_SE] + offset, z1),
+        Vec3::new(x0, heights[terrain::CORNER_SW] + offset, z1),
+        Vec3::new(x0, heights[terrain::CORNER_NW] + offset, z0),
+    ]
+}
+
+/// Outline of a tile's side wall, pushed out from the wall by `offset`.
+fn wall_outline(map: &TileMap, x: u32, y: u32, side: RampDirection, offset: f32) -> [Vec3; 5] {
+    let (top, bottom) = TerrainQuery::new(map).side_heights(x, y, side);
+    let x0 = x as f32 * TILE_SIZE;
+    let z0 = y as f32 * TILE_SIZE;
+    // Wall ends in the order `side_heights` reports them.
+    let (start, end) = match side {
+        RampDirection::North => (
+            Vec2::new(x0, z0 - offset),
+            Vec2::new(x0 + TILE_SIZE, z0 - offset),
+        ),
+        RampDirection::South => (
+            Vec2::new(x0, z0 + TILE_SIZE + offset),
+            Vec2::new(x0 + TILE_SIZE, z0 + TILE_SIZE + offset),
+        ),
+        RampDirection::West => (
+            Vec2::new(x0 - offset, z0),
+            Vec2::new(x0 - offset, z0 + TILE_SIZE),
+        ),
+        RampDirection::East => (
+            Vec2::new(x0 + TILE_SIZE + offset, z0),
+            Vec2::new(x0 + TILE_SIZE + offset, z0 + TILE_SIZE),
+        ),
+    };
+    [
+        Vec3::new(start.x, top[0], start.y),
+        Vec3::new(end.x, top[1], end.y),
+        Vec3::new(end.x, bottom[1], end.y),
+        Vec3::new(start.x, bottom[0], start.y),
+        Vec3::new(start.x, top[0], start.y),
+    ]
+}
+
+fn draw_path_preview(mut gizmos: Gizmos<HoverGizmoGroup>, state: Res<EditorState>) {
+    const PATH_OFFSET: f32 = 0.05;
+    let marker = |gizmos: &mut Gizmos<HoverGizmoGroup>, (x, y): (u32, u32), color: Color| {
+        if x >= state.map.width || y >= state.map.height {
+            return;
+        }
+        let heights = terrain::tile_corner_heights(&state.map, x, y);
+        let center = Vec3::new(
+            (x as f32 + 0.5) * TILE_SIZE,
+            heights.iter().sum::<f32>() / 4.0 + PATH_OFFSET,
+            (y as f32 + 0.5) * TILE_SIZE,
+        );
+        gizmos.circle(center, Dir3::Y, TILE_SIZE * 0.35, color);
+    };
+
+    if let Some(start) = state.path_start {
+        marker(&mut gizmos, start, Color::srgb(0.2, 0.6, 1.0));
+    }
+    if let Some(goal) = state.path_goal {
+        let reachable = state
+            .path_preview
+            .as_ref()
+            .is_some_and(|preview| preview.result.is_some());
+        let color = if reachable {
+            Color::srgb(1.0, 0.8, 0.1)
+        } else {
+            Color::srgb(0.9, 0.2, 0.2)
+        };
+        marker(&mut gizmos, goal, color);
+    }
+
+    if let Some(preview) = state.path_preview.as_ref() {
+        gizmos.linestrip(
+            preview
+                .polyline
+                .iter()
+                .map(|point| *point + Vec3::Y * PATH_OFFSET),
+            Color::srgb(1.0, 0.8, 0.1),
+        );
     }
 }
@@ -1,22 +1,28 @@
 use std::fs::File;
-use std::io::Write;
-use std::path::{Path, PathBuf};
+use std::io::{Cursor, Read, Seek, Write};
+use std::path::{Component, Path, PathBuf};
 
 use anyhow::{Context, Result, anyhow, bail, ensure};
 use bevy::render::mesh::{Indices, Mesh, VertexAttributeValues};
 use bevy::render::texture::Image;
 use image::codecs::png::PngEncoder;
 use image::{ColorType, ExtendedColorType, ImageEncoder};
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 use serde_json::json;
 use zip::CompressionMethod;
+use zip::read::ZipArchive;
 use zip::write::FileOptions;
 
+use crate::io::decode_map;
+use crate::pathing;
+use crate::raster::{RasterExportOptions, RasterMetadata, build_rasters};
 use crate::terrain;
-use crate::terrain::splatmap;
+use crate::terrain::{TopNormals, splatmap};
 use crate::texture::registry::TerrainTextureRegistry;
 use crate::types::{TILE_SIZE, TileMap, TileType};
 
+mod optimize;
+
 const VERTEX_BUFFER_TARGET: u32 = 34962;
 const INDEX_BUFFER_TARGET: u32 = 34963;
 const FLOAT_COMPONENT: u32 = 5126;
@@ -44,42 +50,121 @@ pub struct WallTextureExportDescriptor {
     pub roughness: Option<TextureFileDescriptor>,
 }
 
-#[derive(Serialize)]
-struct MetadataTextureEntry {
-    id: String,
-    diffuse: String,
+/// Terrain texture read back from a package, with paths relative to the asset folder.
+pub struct ImportedTexture {
+    pub tile_type: TileType,
+    pub identifier: String,
+    pub diffuse: String,
+    pub normal: Option<String>,
+    pub roughness: Option<String>,
+}
+
+pub struct ImportedWallTexture {
+    pub identifier: String,
+    pub diffuse: String,
+    pub normal: Option<String>,
+    pub roughness: Option<String>,
+}
+
+pub struct ImportedPackage {
+    pub name: String,
+    pub map: TileMap,
+    pub textures: Vec<ImportedTexture>,
+    pub wall_texture: Option<ImportedWallTexture>,
+}
+
+/// Terrain texture entry of a package's `metadata.json`; paths are inside the package.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct MetadataTextureEntry {
+    pub id: String,
+    pub diffuse: String,
     #[serde(skip_serializing_if = "Option::is_none")]
-    normal: Option<String>,
+    pub normal: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
-    roughness: Option<String>,
-    splatmap_channel: usize,
+    pub roughness: Option<String>,
+    pub splatmap_channel: usize,
 }
 
-#[derive(Serialize)]
-struct MetadataWallTexture {
-    id: String,
-    diffuse: String,
+impl MetadataTextureEntry {
+    /// The tile type this texture belongs to, by identifier or else by splat channel.
+    pub fn tile_type(&self) -> Option<TileType> {
+        TileType::ALL
+            .into_iter()
+            .find(|tile_type| tile_type.identifier() == self.id)
+            .or_else(|| TileType::ALL.get(self.splatmap_channel).copied())
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct MetadataWallTexture {
+    pub id: String,
+    pub diffuse: String,
     #[serde(skip_serializing_if = "Option::is_none")]
-    normal: Option<String>,
+    pub normal: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
-    roughness: Option<String>,
+    pub roughness: Option<String>,
 }
 
-#[derive(Serialize)]
-struct ExportMetadata {
-    name: String,
-    width: u32,
-    height: u32,
-    tile_size: f32,
-    textures: Vec<MetadataTextureEntry>,
-    splatmap: String,
-    mesh: String,
+/// Contents of a package's `metadata.json`.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ExportMetadata {
+    pub name: String,
+    pub width: u32,
+    pub height: u32,
+    pub tile_size: f32,
+    pub textures: Vec<MetadataTextureEntry>,
+    pub splatmap: String,
+    pub mesh: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tilemap: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub walkability: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
-    tilemap: Option<String>,
+    pub wall_texture: Option<MetadataWallTexture>,
     #[serde(skip_serializing_if = "Option::is_none")]
-    wall_texture: Option<MetadataWallTexture>,
+    pub rasters: Option<RasterMetadata>,
+    /// Tiles per chunk edge when `mesh.glb` holds one node per chunk.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mesh_chunk_size: Option<u32>,
+    /// Shading of the tile tops in `mesh.glb`.
+    #[serde(default)]
+    pub top_normals: TopNormals,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mesh_optimization: Option<MeshOptimizationMetadata>,
 }
 
+/// Triangle counts of `mesh.glb` after face merging and LOD generation.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct MeshOptimizationMetadata {
+    /// Triangles the terrain mesh had before merging.
+    pub source_triangles: u32,
+    /// Triangles in the full-detail mesh that was written.
+    pub triangles: u32,
+    /// Fraction of `source_triangles` removed by merging, from 0 to 1.
+    pub reduction: f32,
+    /// Triangles in each simplified LOD level, finest first.
+    #[serde(default)]
+    pub lod_triangles: Vec<u32>,
+}
+
+/// Optional outputs of [`export_package`].
+#[derive(Clone, Copy, Debug, Default)]
+pub struct PackageExportOptions {
+    /// Also write heightmap, tile-type and ramp rasters under `rasters/`.
+    pub rasters: Option<RasterExportOptions>,
+    /// Write `mesh.glb` as one node per [`terrain::CHUNK_SIZE`] chunk so engines can cull them.
+    pub chunked_mesh: bool,
+    /// Flat or smooth shading for tile tops in `mesh.glb`.
+    pub top_normals: TopNormals,
+    /// Merge coplanar tile tops and cliff walls into larger quads.
+    pub optimize_mesh: bool,
+    /// Number of simplified LOD levels (up to [`MAX_MESH_LODS`]) added to `mesh.glb`.
+    pub mesh_lods: u32,
+}
+
+/// Most LOD levels [`export_package`] writes; level `n` flattens 2^n × 2^n tile blocks.
+pub const MAX_MESH_LODS: u32 = 3;
+
 pub fn collect_texture_descriptors(
     map: &TileMap,
     registry: &TerrainTextureRegistry,
@@ -171,6 +256,7 @@ pub fn export_package(
     textures: Vec<TextureExportDescriptor>,
     wall_texture: Option<WallTextureExportDescriptor>,
     splat_png: Vec<u8>,
+    options: PackageExportOptions,
 ) -> Result<()> {
     if let Some(parent) = output_path.parent() {
         if !parent.as_os_str().is_empty() {
@@ -180,13 +266,77 @@ pub fn export_package(
         }
     }
 
-    let mesh = terrain::build_combined_mesh(&map);
-    let mesh_bytes = mesh_to_glb(&mesh)?;
+    let build_meshes = |map: &TileMap| -> Vec<(String, Mesh)> {
+        if options.chunked_mesh {
+            terrain::build_chunk_meshes(map, options.top_normals)
+                .into_iter()
+                .map(|(coords, mesh)| (format!("TerrainChunk_{}_{}", coords.x, coords.y), mesh))
+                .collect()
+        } else {
+            vec![(
+                "Terrain".to_string(),
+                terrain::build_combined_mesh(map, options.top_normals),
+            )]
+        }
+    };
+    let mut nodes: Vec<GlbNode> = build_meshes(&map)
+        .into_iter()
+        .map(|(name, mesh)| GlbNode {
+            name,
+            mesh,
+            lods: Vec::new(),
+        })
+        .collect();
+    let source_triangles = nodes.iter().map(|node| triangle_count(&node.mesh)).sum();
+    if options.optimize_mesh {
+        let merged = optimize::merge_faces(nodes.iter().map(|node| &node.mesh))?;
+        for (node, mesh) in nodes.iter_mut().zip(merged) {
+            node.mesh = mesh;
+        }
+    }
+    let mut lod_triangles = Vec::new();
+    for level in 1..=options.mesh_lods.min(MAX_MESH_LODS) {
+        // LOD maps keep the tile grid, so their chunks line up with the full-detail ones.
+        let lod_map = optimize::lod_map(&map, level);
+        let meshes = build_meshes(&lod_map);
+        let mut triangles = 0;
+        for (node, lod) in nodes
+            .iter_mut()
+            .zip(optimize::merge_faces(meshes.iter().map(|(_, mesh)| mesh))?)
+        {
+            triangles += triangle_count(&lod);
+            node.lods.push(lod);
+        }
+        lod_triangles.push(triangles);
+    }
+    let mesh_optimization = (options.optimize_mesh || !lod_triangles.is_empty()).then(|| {
+        let triangles: u32 = nodes.iter().map(|node| triangle_count(&node.mesh)).sum();
+        MeshOptimizationMetadata {
+            source_triangles,
+            triangles,
+            reduction: 1.0 - triangles as f32 / source_triangles.max(1) as f32,
+            lod_triangles,
+        }
+    });
+    // After merging, since merged faces need tangents of their own.
+    for mesh in nodes
+        .iter_mut()
+        .flat_map(|node| std::iter::once(&mut node.mesh).chain(&mut node.lods))
+    {
+        mesh.generate_tangents()
+            .context("Failed to generate terrain mesh tangents")?;
+    }
+    let mesh_bytes = meshes_to_glb(&nodes)?;
 
     let tilemap_json = serde_json::to_vec_pretty(&map)?;
+    let walkability_bytes = pathing::build_walk_grid(&map).to_bytes();
 
     let (metadata, texture_files, wall_texture_metadata) =
         build_metadata_and_files(&textures, wall_texture)?;
+    let rasters = options
+        .rasters
+        .map(|options| build_rasters(&map, &options, "rasters/"))
+        .transpose()?;
     let metadata = ExportMetadata {
         name: map_name,
         width: map.width,
@@ -196,7 +346,12 @@ pub fn export_package(
         splatmap: "splatmap.png".to_string(),
         mesh: "mesh.glb".to_string(),
         tilemap: Some("tilemap.json".to_string()),
+        walkability: Some("walkability.bin".to_string()),
         wall_texture: wall_texture_metadata,
+        rasters: rasters.as_ref().map(|rasters| rasters.metadata.clone()),
+        mesh_chunk_size: options.chunked_mesh.then_some(terrain::CHUNK_SIZE),
+        top_normals: options.top_normals,
+        mesh_optimization,
     };
     let metadata_json = serde_json::to_vec_pretty(&metadata)?;
 
@@ -211,6 +366,9 @@ pub fn export_package(
     zip.start_file("mesh.glb", options)?;
     zip.write_all(&mesh_bytes)?;
 
+    zip.start_file("walkability.bin", options)?;
+    zip.write_all(&walkability_bytes)?;
+
     zip.start_file("splatmap.png", options)?;
     zip.write_all(&splat_png)?;
 
@@ -226,10 +384,117 @@ pub fn export_package(
         zip.write_all(&bytes)?;
     }
 
+    if let Some(rasters) = rasters {
+        zip.add_directory("rasters/", options)?;
+        for (path, bytes) in rasters.files {
+            zip.start_file(path, options)?;
+            zip.write_all(&bytes)?;
+        }
+    }
+
     zip.finish()?;
     Ok(())
 }
 
+/// Reads a package written by [`export_package`] back into an editable map.
+///
+/// Packaged textures are extracted below `asset_root` into a folder named after the
+/// package contents, so they load through the asset server like the built-in ones and
+/// export again from their new location.
+pub fn import_package(package_path: &Path, asset_root: &Path) -> Result<ImportedPackage> {
+    let package_bytes = std::fs::read(package_path)
+        .with_context(|| format!("Failed to read package {}", package_path.display()))?;
+    let mut zip = ZipArchive::new(Cursor::new(package_bytes.as_slice()))
+        .with_context(|| format!("{} is not a map package", package_path.display()))?;
+
+    let metadata: ExportMetadata =
+        serde_json::from_slice(&read_zip_entry(&mut zip, "metadata.json")?)
+            .context("Invalid package metadata")?;
+    let tilemap_path = metadata.tilemap.as_deref().unwrap_or("tilemap.json");
+    let map = decode_map(&read_zip_entry(&mut zip, tilemap_path)?)
+        .with_context(|| format!("Invalid tile map {tilemap_path} in package"))?;
+
+    // Keying the folder on the package contents keeps re-imports of an edited
+    // package from picking up images the asset server has already cached.
+    let stem = package_path
+        .file_stem()
+        .and_then(|stem| stem.to_str())
+        .unwrap_or("package")
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '_' || c == '-' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect::<String>();
+    let import_dir = format!("imported/{stem}-{:08x}", crc32fast::hash(&package_bytes));
+
+    let mut extract = |packaged: &str| -> Result<String> {
+        ensure!(
+            packaged.starts_with("textures/")
+                && Path::new(packaged)
+                    .components()
+                    .all(|component| matches!(component, Component::Normal(_))),
+            "Package texture path {packaged} is not allowed"
+        );
+        let bytes = read_zip_entry(&mut zip, packaged)?;
+        let asset_path = format!("{import_dir}/{packaged}");
+        let target = asset_root.join(&asset_path);
+        if let Some(parent) = target.parent() {
+            std::fs::create_dir_all(parent)
+                .with_context(|| format!("Failed to create {}", parent.display()))?;
+        }
+        std::fs::write(&target, bytes)
+            .with_context(|| format!("Failed to extract {}", target.display()))?;
+        Ok(asset_path)
+    };
+
+    let mut textures = Vec::new();
+    for entry in &metadata.textures {
+        let tile_type = entry
+            .tile_type()
+            .ok_or_else(|| anyhow!("Package texture {} has no matching tile type", entry.id))?;
+        textures.push(ImportedTexture {
+            tile_type,
+            identifier: entry.id.clone(),
+            diffuse: extract(&entry.diffuse)?,
+            normal: entry.normal.as_deref().map(&mut extract).transpose()?,
+            roughness: entry.roughness.as_deref().map(&mut extract).transpose()?,
+        });
+    }
+
+    let wall_texture = match &metadata.wall_texture {
+        Some(wall) => Some(ImportedWallTexture {
+            identifier: wall.id.clone(),
+            diffuse: extract(&wall.diffuse)?,
+            normal: wall.normal.as_deref().map(&mut extract).transpose()?,
+            roughness: wall.roughness.as_deref().map(&mut extract).transpose()?,
+        }),
+        None => None,
+    };
+
+    Ok(ImportedPackage {
+        name: metadata.name,
+        map,
+        textures,
+        wall_texture,
+    })
+}
+
+pub(crate) fn read_zip_entry<R: Read + Seek>(
+    zip: &mut ZipArchive<R>,
+    name: &str,
+) -> Result<Vec<u8>> {
+    let mut file = zip
+        .by_name(name)
+        .with_context(|| format!("Package is missing {name}"))?;
+    let mut bytes = Vec::with_capacity(file.size() as usize);
+    file.read_to_end(&mut bytes)?;
+    Ok(bytes)
+}
+
 fn build_metadata_and_files(
     textures: &[TextureExportDescriptor],
     wall_texture: Option<WallTextureExportDescriptor>,
@@ -397,12 +662,122 @@ pub fn build_map_splatmap_png(map: &TileMap) -> Result<Vec<u8>> {
     encode_splatmap_png(&image)
 }
 
-fn mesh_to_glb(mesh: &Mesh) -> Result<Vec<u8>> {
+/// A `mesh.glb` node with its simplified LOD meshes, finest first.
+struct GlbNode {
+    name: String,
+    mesh: Mesh,
+    lods: Vec<Mesh>,
+}
+
+fn triangle_count(mesh: &Mesh) -> u32 {
+    mesh.indices().map_or(0, |indices| indices.len() as u32 / 3)
+}
+
+/// Writes each node's mesh as its own glTF mesh and node. Several nodes are grouped
+/// under a parent `Terrain` node; a single one becomes the `Terrain` node itself. LOD
+/// meshes get nodes outside the scene, referenced through the `MSFT_lod` extension.
+fn meshes_to_glb(nodes: &[GlbNode]) -> Result<Vec<u8>> {
+    ensure!(!nodes.is_empty(), "Mesh export requires at least one mesh");
+    let mut writer = BufferWriter::default();
+    let mut gltf_meshes = Vec::new();
+    let mut push_mesh = |name: &str, mesh: &Mesh| -> Result<usize> {
+        let primitive = push_primitive(&mut writer, mesh)?;
+        gltf_meshes.push(json!({
+            "name": name,
+            "primitives": [primitive],
+        }));
+        Ok(gltf_meshes.len() - 1)
+    };
+
+    let mut gltf_nodes: Vec<serde_json::Value> = Vec::new();
+    if nodes.len() > 1 {
+        gltf_nodes.push(json!({
+            "name": "Terrain",
+            "children": (1..=nodes.len()).collect::<Vec<_>>(),
+        }));
+    }
+    let mut lod_nodes = Vec::new();
+    let first_lod_node = gltf_nodes.len() + nodes.len();
+    for node in nodes {
+        let name = if nodes.len() == 1 {
+            "Terrain"
+        } else {
+            node.name.as_str()
+        };
+        let mut gltf_node = json!({
+            "mesh": push_mesh(name, &node.mesh)?,
+            "name": name,
+        });
+        if !node.lods.is_empty() {
+            let ids: Vec<usize> = (0..node.lods.len())
+                .map(|level| first_lod_node + lod_nodes.len() + level)
+                .collect();
+            for (level, lod) in node.lods.iter().enumerate() {
+                let lod_name = format!("{name}_LOD{}", level + 1);
+                lod_nodes.push(json!({
+                    "mesh": push_mesh(&lod_name, lod)?,
+                    "name": lod_name,
+                }));
+            }
+            gltf_node["extensions"] = json!({ "MSFT_lod": { "ids": ids } });
+        }
+        gltf_nodes.push(gltf_node);
+    }
+    let has_lods = !lod_nodes.is_empty();
+    gltf_nodes.extend(lod_nodes);
+    let (mut bin, buffer_views, accessors) = writer.finish();
+
+    let mut root = json!({
+        "asset": {
+            "version": "2.0",
+            "generator": "tilemapedit3d exporter",
+        },
+        "buffers": [{
+            "byteLength": bin.len() as u64,
+            "name": "TerrainBuffer",
+        }],
+        "bufferViews": buffer_views,
+        "accessors": accessors,
+        "meshes": gltf_meshes,
+        "nodes": gltf_nodes,
+        "scenes": [{
+            "nodes": [0],
+        }],
+        "scene": 0,
+    });
+    if has_lods {
+        root["extensionsUsed"] = json!(["MSFT_lod"]);
+    }
+
+    let mut json_bytes = serde_json::to_vec(&root)?;
+    pad_to_four(&mut json_bytes, b' ');
+    pad_to_four(&mut bin, 0);
+
+    let total_length = 12 + 8 + json_bytes.len() + 8 + bin.len();
+    let mut glb = Vec::with_capacity(total_length);
+    glb.extend_from_slice(&0x46546C67u32.to_le_bytes());
+    glb.extend_from_slice(&2u32.to_le_bytes());
+    glb.extend_from_slice(&(total_length as u32).to_le_bytes());
+
+    glb.extend_from_slice(&(json_bytes.len() as u32).to_le_bytes());
+    glb.extend_from_slice(&0x4E4F534Au32.to_le_bytes());
+    glb.extend_from_slice(&json_bytes);
+
+    glb.extend_from_slice(&(bin.len() as u32).to_le_bytes());
+    glb.extend_from_slice(&0x004E4942u32.to_le_bytes());
+    glb.extend_from_slice(&bin);
+
+    Ok(glb)
+}
+
+/// Writes a mesh's attributes and indices to the buffer and returns its glTF primitive.
+fn push_primitive(writer: &mut BufferWriter, mesh: &Mesh) -> Result<serde_json::Value> {
     let positions = extract_vec3(mesh, Mesh::ATTRIBUTE_POSITION, "POSITION")?;
     let normals = extract_vec3(mesh, Mesh::ATTRIBUTE_NORMAL, "NORMAL")?;
     let texcoords = extract_vec2(mesh, Mesh::ATTRIBUTE_UV_0, "TEXCOORD_0")?;
     let texcoords1 = extract_optional_vec2(mesh, Mesh::ATTRIBUTE_UV_1)?;
     let colors = extract_optional_vec4(mesh, Mesh::ATTRIBUTE_COLOR)?;
+    let tangents = extract_optional_vec4(mesh, Mesh::ATTRIBUTE_TANGENT)?;
     let indices = extract_indices(mesh)?;
 
     ensure!(
@@ -425,8 +800,13 @@ fn mesh_to_glb(mesh: &Mesh) -> Result<Vec<u8>> {
             "Vertex color count must match vertices"
         );
     }
+    if let Some(ref tangents) = tangents {
+        ensure!(
+            tangents.len() == positions.len(),
+            "Tangent count must match vertices"
+        );
+    }
 
-    let mut writer = BufferWriter::default();
     let position_accessor = writer.push_vec3(&positions, true)?;
     let normal_accessor = writer.push_vec3(&normals, false)?;
     let tex_accessor = writer.push_vec2(&texcoords)?;
@@ -438,10 +818,12 @@ fn mesh_to_glb(mesh: &Mesh) -> Result<Vec<u8>> {
         .as_ref()
         .map(|cols| writer.push_vec4(cols))
         .transpose()?;
+    let tangent_accessor = tangents
+        .as_ref()
+        .map(|tangents| writer.push_vec4(tangents))
+        .transpose()?;
     let index_accessor = writer.push_indices(&indices)?;
 
-    let (mut bin, buffer_views, accessors) = writer.finish();
-
     let mut attributes = serde_json::Map::new();
     attributes.insert("POSITION".to_string(), json!(position_accessor));
     attributes.insert("NORMAL".to_string(), json!(normal_accessor));
@@ -452,57 +834,15 @@ fn mesh_to_glb(mesh: &Mesh) -> Result<Vec<u8>> {
     if let Some(accessor) = color_accessor {
         attributes.insert("COLOR_0".to_string(), json!(accessor));
     }
+    if let Some(accessor) = tangent_accessor {
+        attributes.insert("TANGENT".to_string(), json!(accessor));
+    }
 
-    let primitive = json!({
+    Ok(json!({
         "attributes": attributes,
         "indices": index_accessor,
         "mode": 4,
-    });
-
-    let root = json!({
-        "asset": {
-            "version": "2.0",
-            "generator": "tilemapedit3d exporter",
-        },
-        "buffers": [{
-            "byteLength": bin.len() as u64,
-            "name": "TerrainBuffer",
-        }],
-        "bufferViews": buffer_views,
-        "accessors": accessors,
-        "meshes": [{
-            "name": "Terrain",
-            "primitives": [primitive],
-        }],
-        "nodes": [{
-            "mesh": 0,
-            "name": "Terrain",
-        }],
-        "scenes": [{
-            "nodes": [0],
-        }],
-        "scene": 0,
-    });
-
-    let mut json_bytes = serde_json::to_vec(&root)?;
-    pad_to_four(&mut json_bytes, b' ');
-    pad_to_four(&mut bin, 0);
-
-    let total_length = 12 + 8 + json_bytes.len() + 8 + bin.len();
-    let mut glb = Vec::with_capacity(total_length);
-    glb.extend_from_slice(&0x46546C67u32.to_le_bytes());
-    glb.extend_from_slice(&2u32.to_le_bytes());
-    glb.extend_from_slice(&(total_length as u32).to_le_bytes());
-
-    glb.extend_from_slice(&(json_bytes.len() as u32).to_le_bytes());
-    glb.extend_from_slice(&0x4E4F534Au32.to_le_bytes());
-    glb.extend_from_slice(&json_bytes);
-
-    glb.extend_from_slice(&(bin.len() as u32).to_le_bytes());
-    glb.extend_from_slice(&0x004E4942u32.to_le_bytes());
-    glb.extend_from_slice(&bin);
-
-    Ok(glb)
+    }))
 }
 
 fn pad_to_four(buffer: &mut Vec<u8>, pad: u8) {
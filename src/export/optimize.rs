@@ -0,0 +1,888 @@
+//! Export-only mesh reductions: merging coplanar faces into larger quads and the
+//! flattened maps the LOD meshes are built from.
+
+use super::*;
+use crate::types::{TileKind, TileRect};
+use bevy::math::{Vec2, Vec3};
+use std::collections::{HashMap, HashSet};
+
+/// How far a position may sit from a tile grid line and still count as on it.
+const GRID_EPSILON: f32 = 1e-3;
+/// Attribute values are compared after rounding to this many steps per unit.
+const KEY_STEPS: f32 = 1e4;
+
+#[derive(Clone, Copy)]
+struct Vertex {
+    position: Vec3,
+    normal: Vec3,
+    uv: Vec2,
+    layer: [f32; 2],
+    color: [f32; 4],
+}
+
+/// A tile-wide slice of a cliff wall: the wall plane (`x` or `z` held at `line`
+/// tiles), the tile along it and the side it faces.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+struct WallSlot {
+    along_x: bool,
+    line: i32,
+    facing_positive: bool,
+    segment: i32,
+}
+
+/// A mergeable face: what must match for two neighbours to merge, and the vertex
+/// data to rebuild the merged face from. Heights and UVs are stored as linear
+/// functions of the grid coordinates, so any corner of a merged quad can be evaluated.
+struct Surface {
+    key: Vec<i64>,
+    template: Vertex,
+    /// Height at grid origin, then per step along the two face axes.
+    height: [f32; 3],
+    uv: [Vec2; 3],
+}
+
+impl Surface {
+    fn new(template: Vertex, height: [f32; 3], uv: [Vec2; 3]) -> Self {
+        let values = template
+            .normal
+            .to_array()
+            .into_iter()
+            .chain(template.layer)
+            .chain(template.color)
+            .chain(height)
+            .chain(uv.iter().flat_map(|value| value.to_array()));
+        Self {
+            key: values
+                .map(|value| (value * KEY_STEPS).round() as i64)
+                .collect(),
+            template,
+            height,
+            uv,
+        }
+    }
+
+    fn vertex(&self, position: Vec3, a: f32, b: f32) -> Vertex {
+        Vertex {
+            position,
+            uv: self.uv[0] + self.uv[1] * a + self.uv[2] * b,
+            ..self.template
+        }
+    }
+}
+
+/// A mesh's faces after merging: the triangles kept as they were and the merged
+/// quads, which are only triangulated once every mesh's vertices are known.
+struct MergedMesh {
+    triangles: Vec<[Vertex; 3]>,
+    /// Corners in winding order, facing the quad's normal.
+    quads: Vec<[Vertex; 4]>,
+    layers: bool,
+    colors: bool,
+}
+
+/// Merges tile tops that share a plane and attributes into rectangles, and runs of
+/// rectangular cliff wall slices into single quads, in meshes drawn side by side
+/// (e.g. the chunks of one level of detail). Everything else is kept as is.
+///
+/// A merged edge is split at every vertex of any of the meshes that lies on it, so
+/// neighbours that were merged differently, or not at all, meet it vertex to vertex
+/// instead of leaving T-junctions that show as hairline cracks.
+pub(super) fn merge_faces<'a>(meshes: impl IntoIterator<Item = &'a Mesh>) -> Result<Vec<Mesh>> {
+    let merged = meshes
+        .into_iter()
+        .map(merge_mesh)
+        .collect::<Result<Vec<_>>>()?;
+
+    let mut points = GridPoints::default();
+    for mesh in &merged {
+        for vertex in mesh
+            .triangles
+            .iter()
+            .flatten()
+            .chain(mesh.quads.iter().flatten())
+        {
+            points.insert(vertex.position);
+        }
+    }
+
+    Ok(merged
+        .into_iter()
+        .map(|mesh| {
+            let mut triangles = mesh.triangles;
+            for quad in &mesh.quads {
+                push_split_quad(&mut triangles, quad, &points);
+            }
+            triangles_to_mesh(&triangles, mesh.layers, mesh.colors)
+        })
+        .collect())
+}
+
+fn merge_mesh(mesh: &Mesh) -> Result<MergedMesh> {
+    let positions = extract_vec3(mesh, Mesh::ATTRIBUTE_POSITION, "POSITION")?;
+    let normals = extract_vec3(mesh, Mesh::ATTRIBUTE_NORMAL, "NORMAL")?;
+    let uvs = extract_vec2(mesh, Mesh::ATTRIBUTE_UV_0, "TEXCOORD_0")?;
+    let layers = extract_optional_vec2(mesh, Mesh::ATTRIBUTE_UV_1)?;
+    let colors = extract_optional_vec4(mesh, Mesh::ATTRIBUTE_COLOR)?;
+    let indices = extract_indices(mesh)?;
+    ensure!(
+        positions.len() == normals.len() && positions.len() == uvs.len(),
+        "Mesh optimization requires matching attribute counts"
+    );
+
+    let vertices: Vec<Vertex> = (0..positions.len())
+        .map(|index| Vertex {
+            position: Vec3::from(positions[index]),
+            normal: Vec3::from(normals[index]),
+            uv: Vec2::from(uvs[index]),
+            layer: layers.as_ref().map_or([0.0; 2], |layers| layers[index]),
+            color: colors.as_ref().map_or([0.0; 4], |colors| colors[index]),
+        })
+        .collect();
+
+    let mut tops: HashMap<(i32, i32), Vec<[Vertex; 3]>> = HashMap::new();
+    let mut walls: HashMap<WallSlot, Vec<[Vertex; 3]>> = HashMap::new();
+    let mut triangles = Vec::with_capacity(indices.len() / 3);
+    for triangle in indices.chunks_exact(3) {
+        let triangle = [0, 1, 2].map(|corner| vertices[triangle[corner] as usize]);
+        if let Some(cell) = top_cell(&triangle) {
+            tops.entry(cell).or_default().push(triangle);
+        } else if let Some(slot) = wall_slot(&triangle) {
+            walls.entry(slot).or_default().push(triangle);
+        } else {
+            triangles.push(triangle);
+        }
+    }
+
+    let mut top_surfaces = HashMap::new();
+    for (cell, cell_triangles) in tops {
+        match top_surface(cell, &cell_triangles) {
+            Some(surface) => {
+                top_surfaces.insert(cell, surface);
+            }
+            None => triangles.extend(cell_triangles),
+        }
+    }
+    let mut quads = Vec::new();
+    merge_tops(&top_surfaces, &mut quads);
+
+    let mut wall_surfaces = Vec::new();
+    for (slot, slot_triangles) in walls {
+        match wall_surface(slot, &slot_triangles) {
+            Some(surface) => wall_surfaces.push((slot, surface)),
+            None => triangles.extend(slot_triangles),
+        }
+    }
+    merge_walls(wall_surfaces, &mut quads);
+
+    Ok(MergedMesh {
+        triangles,
+        quads,
+        layers: layers.is_some(),
+        colors: colors.is_some(),
+    })
+}
+
+fn grid(value: f32) -> Option<i32> {
+    let steps = value / TILE_SIZE;
+    let rounded = steps.round();
+    ((steps - rounded).abs() < GRID_EPSILON).then_some(rounded as i32)
+}
+
+fn face_normal(triangle: &[Vertex; 3]) -> Vec3 {
+    let [a, b, c] = triangle.map(|vertex| vertex.position);
+    (b - a).cross(c - a)
+}
+
+/// The tile a triangle covers half of, if it is a non-vertical face spanning one tile.
+fn top_cell(triangle: &[Vertex; 3]) -> Option<(i32, i32)> {
+    let normal = face_normal(triangle);
+    if normal.y.abs() <= GRID_EPSILON * normal.length() {
+        return None;
+    }
+    let mut corners = [(0, 0); 3];
+    for (corner, vertex) in corners.iter_mut().zip(triangle) {
+        *corner = (grid(vertex.position.x)?, grid(vertex.position.z)?);
+    }
+    let x = corners.iter().map(|corner| corner.0).min()?;
+    let z = corners.iter().map(|corner| corner.1).min()?;
+    corners
+        .iter()
+        .all(|&(cx, cz)| cx - x <= 1 && cz - z <= 1)
+        .then_some((x, z))
+}
+
+/// The wall slot of a vertical triangle lying on a tile edge and spanning one tile.
+fn wall_slot(triangle: &[Vertex; 3]) -> Option<WallSlot> {
+    let normal = face_normal(triangle);
+    if normal.y.abs() > GRID_EPSILON * normal.length() {
+        return None;
+    }
+    let along_x = normal.z.abs() > normal.x.abs();
+    let line = grid(wall_coords(along_x, triangle[0].position).0)?;
+    let mut segments = [0; 3];
+    for (segment, vertex) in segments.iter_mut().zip(triangle) {
+        let (line_coord, along) = wall_coords(along_x, vertex.position);
+        if grid(line_coord)? != line {
+            return None;
+        }
+        *segment = grid(along)?;
+    }
+    let segment = *segments.iter().min()?;
+    if segments.iter().any(|&value| value - segment > 1) {
+        return None;
+    }
+    let facing = if along_x { normal.z } else { normal.x };
+    Some(WallSlot {
+        along_x,
+        line,
+        facing_positive: facing > 0.0,
+        segment,
+    })
+}
+
+/// A position's coordinate on the wall plane's fixed axis and on the axis it runs along.
+fn wall_coords(along_x: bool, position: Vec3) -> (f32, f32) {
+    if along_x {
+        (position.z, position.x)
+    } else {
+        (position.x, position.z)
+    }
+}
+
+/// Finds the vertex at each corner of a face, keyed by `corner_of`. Returns `None`
+/// unless the triangles use exactly those corners with one attribute set per corner.
+fn collect_corners<const N: usize>(
+    triangles: &[[Vertex; 3]],
+    corner_of: impl Fn(&Vertex) -> Option<usize>,
+) -> Option<[Vertex; N]> {
+    let mut corners: [Option<Vertex>; N] = [None; N];
+    for vertex in triangles.iter().flatten() {
+        let slot = &mut corners[corner_of(vertex)?];
+        match slot {
+            Some(existing) if !same_vertex(existing, vertex) => return None,
+            Some(_) => {}
+            None => *slot = Some(*vertex),
+        }
+    }
+    let corners = corners.map(|corner| corner.ok_or(()));
+    corners
+        .iter()
+        .all(Result::is_ok)
+        .then(|| corners.map(Result::unwrap))
+}
+
+fn same_vertex(a: &Vertex, b: &Vertex) -> bool {
+    a.position.abs_diff_eq(b.position, GRID_EPSILON)
+        && a.normal.abs_diff_eq(b.normal, 1.0 / KEY_STEPS)
+        && a.uv.abs_diff_eq(b.uv, 1.0 / KEY_STEPS)
+        && a.layer == b.layer
+        && a.color == b.color
+}
+
+fn same_attributes(vertices: &[Vertex]) -> bool {
+    vertices.windows(2).all(|pair| {
+        pair[0].normal.abs_diff_eq(pair[1].normal, 1.0 / KEY_STEPS)
+            && pair[0].layer == pair[1].layer
+            && pair[0].color == pair[1].color
+    })
+}
+
+/// Describes a tile top as a plane in grid coordinates, if its two triangles are
+/// coplanar and share every attribute except position and (affine) UVs.
+fn top_surface((x, z): (i32, i32), triangles: &[[Vertex; 3]]) -> Option<Surface> {
+    if triangles.len() != 2 {
+        return None;
+    }
+    // Corners in the order (x, z), (x + 1, z), (x, z + 1), (x + 1, z + 1).
+    let corners: [Vertex; 4] = collect_corners(triangles, |vertex| {
+        let dx = grid(vertex.position.x)? - x;
+        let dz = grid(vertex.position.z)? - z;
+        Some((dz * 2 + dx) as usize)
+    })?;
+    if !same_attributes(&corners) {
+        return None;
+    }
+
+    let [origin, step_x, step_z, far] = corners;
+    let slope_x = step_x.position.y - origin.position.y;
+    let slope_z = step_z.position.y - origin.position.y;
+    let uv_x = step_x.uv - origin.uv;
+    let uv_z = step_z.uv - origin.uv;
+    let planar = (far.position.y - (origin.position.y + slope_x + slope_z)).abs() < GRID_EPSILON;
+    let affine_uv = far.uv.abs_diff_eq(origin.uv + uv_x + uv_z, 1.0 / KEY_STEPS);
+    if !planar || !affine_uv {
+        return None;
+    }
+
+    let (x, z) = (x as f32, z as f32);
+    Some(Surface::new(
+        origin,
+        [
+            origin.position.y - slope_x * x - slope_z * z,
+            slope_x,
+            slope_z,
+        ],
+        [origin.uv - uv_x * x - uv_z * z, uv_x, uv_z],
+    ))
+}
+
+/// Describes a wall slice as a rectangle, if it is one: flat top and bottom edges
+/// and shared attributes apart from position and (affine) UVs. The height holds the
+/// top and bottom edge instead of a slope.
+fn wall_surface(slot: WallSlot, triangles: &[[Vertex; 3]]) -> Option<Surface> {
+    if triangles.len() != 2 {
+        return None;
+    }
+    let top = triangles
+        .iter()
+        .flatten()
+        .map(|vertex| vertex.position.y)
+        .fold(f32::NEG_INFINITY, f32::max);
+    let bottom = triangles
+        .iter()
+        .flatten()
+        .map(|vertex| vertex.position.y)
+        .fold(f32::INFINITY, f32::min);
+    // Corners in the order (start, top), (end, top), (start, bottom), (end, bottom).
+    let corners: [Vertex; 4] = collect_corners(triangles, |vertex| {
+        let end = grid(wall_coords(slot.along_x, vertex.position).1)? - slot.segment;
+        let y = vertex.position.y;
+        let row = if (y - top).abs() < GRID_EPSILON {
+            0
+        } else if (y - bottom).abs() < GRID_EPSILON {
+            1
+        } else {
+            return None;
+        };
+        Some((row * 2 + end) as usize)
+    })?;
+    if !same_attributes(&corners) {
+        return None;
+    }
+
+    let [start_top, end_top, start_bottom, end_bottom] = corners;
+    let uv_along = end_top.uv - start_top.uv;
+    let uv_down = start_bottom.uv - start_top.uv;
+    if !end_bottom
+        .uv
+        .abs_diff_eq(start_top.uv + uv_along + uv_down, 1.0 / KEY_STEPS)
+    {
+        return None;
+    }
+
+    let segment = slot.segment as f32;
+    Some(Surface::new(
+        start_top,
+        [top, bottom, 0.0],
+        [start_top.uv - uv_along * segment, uv_along, uv_down],
+    ))
+}
+
+fn merge_tops(surfaces: &HashMap<(i32, i32), Surface>, quads: &mut Vec<[Vertex; 4]>) {
+    let mut cells: Vec<(i32, i32)> = surfaces.keys().copied().collect();
+    cells.sort_unstable_by_key(|&(x, z)| (z, x));
+    let mut merged = HashSet::new();
+    for (x, z) in cells {
+        if merged.contains(&(x, z)) {
+            continue;
+        }
+        let surface = &surfaces[&(x, z)];
+        let matches = |cell: (i32, i32)| {
+            !merged.contains(&cell)
+                && surfaces
+                    .get(&cell)
+                    .is_some_and(|other| other.key == surface.key)
+        };
+        let mut width = 1;
+        while matches((x + width, z)) {
+            width += 1;
+        }
+        let mut depth = 1;
+        while (0..width).all(|dx| matches((x + dx, z + depth))) {
+            depth += 1;
+        }
+        for dz in 0..depth {
+            for dx in 0..width {
+                merged.insert((x + dx, z + dz));
+            }
+        }
+
+        let corner = |gx: i32, gz: i32| {
+            let (gx, gz) = (gx as f32, gz as f32);
+            let y = surface.height[0] + surface.height[1] * gx + surface.height[2] * gz;
+            surface.vertex(Vec3::new(gx * TILE_SIZE, y, gz * TILE_SIZE), gx, gz)
+        };
+        push_quad(
+            quads,
+            [
+                corner(x, z),
+                corner(x, z + depth),
+                corner(x + width, z + depth),
+                corner(x + width, z),
+            ],
+            surface.template.normal,
+        );
+    }
+}
+
+fn merge_walls(mut surfaces: Vec<(WallSlot, Surface)>, quads: &mut Vec<[Vertex; 4]>) {
+    surfaces.sort_unstable_by_key(|(slot, _)| *slot);
+    let mut index = 0;
+    while index < surfaces.len() {
+        let (start, surface) = &surfaces[index];
+        let mut end = index + 1;
+        while let Some((slot, other)) = surfaces.get(end)
+            && slot.along_x == start.along_x
+            && slot.line == start.line
+            && slot.facing_positive == start.facing_positive
+            && slot.segment == start.segment + (end - index) as i32
+            && other.key == surface.key
+        {
+            end += 1;
+        }
+
+        let line = start.line as f32 * TILE_SIZE;
+        let [top, bottom, _] = surface.height;
+        let corner = |segment: i32, y: f32| {
+            let along = segment as f32 * TILE_SIZE;
+            let position = if start.along_x {
+                Vec3::new(along, y, line)
+            } else {
+                Vec3::new(line, y, along)
+            };
+            surface.vertex(position, segment as f32, if y == top { 0.0 } else { 1.0 })
+        };
+        let last = start.segment + (end - index) as i32;
+        push_quad(
+            quads,
+            [
+                corner(start.segment, top),
+                corner(last, top),
+                corner(last, bottom),
+                corner(start.segment, bottom),
+            ],
+            surface.template.normal,
+        );
+        index = end;
+    }
+}
+
+/// Pushes a merged quad, its corners reversed if needed so it faces `normal`.
+fn push_quad(quads: &mut Vec<[Vertex; 4]>, mut quad: [Vertex; 4], normal: Vec3) {
+    if face_normal(&[quad[0], quad[1], quad[2]]).dot(normal) < 0.0 {
+        quad.reverse();
+    }
+    quads.push(quad);
+}
+
+/// Vertex positions by the tile grid corner they sit on, for finding the vertices
+/// that lie on a merged edge.
+#[derive(Default)]
+struct GridPoints {
+    points: HashMap<(i32, i32), Vec<Vec3>>,
+}
+
+impl GridPoints {
+    fn insert(&mut self, position: Vec3) {
+        let (Some(x), Some(z)) = (grid(position.x), grid(position.z)) else {
+            return;
+        };
+        let points = self.points.entry((x, z)).or_default();
+        if !points
+            .iter()
+            .any(|point| point.abs_diff_eq(position, GRID_EPSILON))
+        {
+            points.push(position);
+        }
+    }
+
+    /// The points strictly inside the edge from `a` to `b`, ordered from `a`, each with
+    /// its fraction of the way along. Edges must run between grid corners.
+    fn on_edge(&self, a: Vec3, b: Vec3) -> Vec<(f32, Vec3)> {
+        let (Some(ax), Some(az), Some(bx), Some(bz)) = (grid(a.x), grid(a.z), grid(b.x), grid(b.z))
+        else {
+            return Vec::new();
+        };
+        // The grid corners an edge passes over are evenly spaced along it; a vertical
+        // edge stays on one corner.
+        let steps = gcd((bx - ax).unsigned_abs(), (bz - az).unsigned_abs()) as i32;
+        let (step_x, step_z) = if steps == 0 {
+            (0, 0)
+        } else {
+            ((bx - ax) / steps, (bz - az) / steps)
+        };
+        let edge = b - a;
+        let mut found = Vec::new();
+        for step in 0..=steps {
+            let corner = (ax + step_x * step, az + step_z * step);
+            for &point in self.points.get(&corner).into_iter().flatten() {
+                if point.abs_diff_eq(a, GRID_EPSILON) || point.abs_diff_eq(b, GRID_EPSILON) {
+                    continue;
+                }
+                let t = (point - a).dot(edge) / edge.length_squared();
+                if (0.0..=1.0).contains(&t) && (a + edge * t).abs_diff_eq(point, GRID_EPSILON) {
+                    found.push((t, point));
+                }
+            }
+        }
+        found.sort_by(|x, y| x.0.total_cmp(&y.0));
+        found
+    }
+}
+
+fn gcd(a: u32, b: u32) -> u32 {
+    if b == 0 { a } else { gcd(b, a % b) }
+}
+
+/// Triangulates a merged quad with extra vertices wherever `points` lie on its edges.
+/// The fan starts at a corner whose two edges are whole, so no triangle is degenerate,
+/// or at the quad's centre when every corner touches a split edge.
+fn push_split_quad(triangles: &mut Vec<[Vertex; 3]>, quad: &[Vertex; 4], points: &GridPoints) {
+    let mut outline = Vec::new();
+    let mut corners = [0; 4];
+    let mut split = [false; 4];
+    for index in 0..4 {
+        let (a, b) = (quad[index], quad[(index + 1) % 4]);
+        corners[index] = outline.len();
+        outline.push(a);
+        let on_edge = points.on_edge(a.position, b.position);
+        split[index] = !on_edge.is_empty();
+        outline.extend(on_edge.into_iter().map(|(t, position)| Vertex {
+            position,
+            uv: a.uv.lerp(b.uv, t),
+            ..a
+        }));
+    }
+
+    match (0..4).find(|&index| !split[index] && !split[(index + 3) % 4]) {
+        Some(index) => {
+            outline.rotate_left(corners[index]);
+            for pair in outline[1..].windows(2) {
+                triangles.push([outline[0], pair[0], pair[1]]);
+            }
+        }
+        None => {
+            let centre = Vertex {
+                position: quad.iter().map(|vertex| vertex.position).sum::<Vec3>() / 4.0,
+                uv: quad.iter().map(|vertex| vertex.uv).sum::<Vec2>() / 4.0,
+                ..quad[0]
+            };
+            for index in 0..outline.len() {
+                triangles.push([centre, outline[index], outline[(index + 1) % outline.len()]]);
+            }
+        }
+    }
+}
+
+fn triangles_to_mesh(triangles: &[[Vertex; 3]], layers: bool, colors: bool) -> Mesh {
+    let mut lookup: HashMap<[u32; 14], u32> = HashMap::new();
+    let mut positions = Vec::new();
+    let mut normals = Vec::new();
+    let mut uvs = Vec::new();
+    let mut tile_layers = Vec::new();
+    let mut vertex_colors = Vec::new();
+    let mut indices = Vec::with_capacity(triangles.len() * 3);
+    for vertex in triangles.iter().flatten() {
+        let mut key = [0u32; 14];
+        let values = vertex
+            .position
+            .to_array()
+            .into_iter()
+            .chain(vertex.normal.to_array())
+            .chain(vertex.uv.to_array())
+            .chain(vertex.layer)
+            .chain(vertex.color);
+        for (slot, value) in key.iter_mut().zip(values) {
+            *slot = value.to_bits();
+        }
+        let index = *lookup.entry(key).or_insert_with(|| {
+            positions.push(vertex.position.to_array());
+            normals.push(vertex.normal.to_array());
+            uvs.push(vertex.uv.to_array());
+            tile_layers.push(vertex.layer);
+            vertex_colors.push(vertex.color);
+            positions.len() as u32 - 1
+        });
+        indices.push(index);
+    }
+
+    let mut mesh = terrain::empty_mesh();
+    mesh.insert_attribute(Mesh::ATTRIBUTE_POSITION, positions);
+    mesh.insert_attribute(Mesh::ATTRIBUTE_NORMAL, normals);
+    mesh.insert_attribute(Mesh::ATTRIBUTE_UV_0, uvs);
+    if layers {
+        mesh.insert_attribute(Mesh::ATTRIBUTE_UV_1, tile_layers);
+    }
+    if colors {
+        mesh.insert_attribute(Mesh::ATTRIBUTE_COLOR, vertex_colors);
+    }
+    mesh.insert_indices(Indices::U32(indices));
+    mesh
+}
+
+/// Copy of `map` where every `2^level` square block of tiles is flattened to its most
+/// common elevation (the higher one on ties) and most common tile type at that
+/// elevation. Ramps become floor, so cliffs replace them at a distance.
+pub(super) fn lod_map(map: &TileMap, level: u32) -> TileMap {
+    let block = 1u32 << level;
+    let mut lod = map.clone();
+    for block_y in (0..map.height).step_by(block as usize) {
+        for block_x in (0..map.width).step_by(block as usize) {
+            let rect = TileRect {
+                x0: block_x,
+                y0: block_y,
+                x1: (block_x + block).min(map.width),
+                y1: (block_y + block).min(map.height),
+            };
+            let mut elevations: HashMap<i8, u32> = HashMap::new();
+            for (x, y) in rect.tiles() {
+                *elevations.entry(map.get(x, y).elevation).or_default() += 1;
+            }
+            let Some(elevation) = elevations
+                .into_iter()
+                .max_by_key(|&(elevation, count)| (count, elevation))
+                .map(|(elevation, _)| elevation)
+            else {
+                continue;
+            };
+            let mut types: HashMap<TileType, u32> = HashMap::new();
+            for (x, y) in rect.tiles() {
+                let tile = map.get(x, y);
+                if tile.elevation == elevation {
+                    *types.entry(tile.tile_type).or_default() += 1;
+                }
+            }
+            let tile_type = types
+                .into_iter()
+                .max_by_key(|&(tile_type, count)| (count, std::cmp::Reverse(tile_type.as_index())))
+                .map_or(TileType::default(), |(tile_type, _)| tile_type);
+
+            for (x, y) in rect.tiles() {
+                let index = lod.idx(x, y);
+                let tile = &mut lod.tiles[index];
+                tile.kind = TileKind::Floor;
+                tile.ramp_direction = None;
+                tile.elevation = elevation;
+                tile.tile_type = tile_type;
+            }
+        }
+    }
+    lod
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::terrain::CHUNK_SIZE;
+    use crate::types::RampDirection;
+
+    fn set_tile(map: &mut TileMap, x: u32, y: u32, elevation: i8, tile_type: TileType) {
+        let index = map.idx(x, y);
+        map.tiles[index].elevation = elevation;
+        map.tiles[index].tile_type = tile_type;
+    }
+
+    /// Four chunks with plateaus, ramps and texture patches, some of them crossing
+    /// chunk borders.
+    fn sample_map() -> TileMap {
+        let mut map = TileMap::new(CHUNK_SIZE + 8, CHUNK_SIZE + 4);
+        for y in 4..12 {
+            for x in 5..14 {
+                let tile_type = if x < 9 {
+                    TileType::Rock
+                } else {
+                    TileType::Sand
+                };
+                set_tile(&mut map, x, y, 2, tile_type);
+            }
+        }
+        for y in 20..30 {
+            for x in 28..38 {
+                set_tile(&mut map, x, y, 1, TileType::Dirt);
+            }
+        }
+        for (x, y) in [(3, 3), (20, 7), (30, 33), (31, 33)] {
+            set_tile(&mut map, x, y, 0, TileType::Rock);
+        }
+        for (x, y, direction) in [
+            (5, 8, RampDirection::West),
+            (10, 11, RampDirection::South),
+            (28, 25, RampDirection::West),
+        ] {
+            let index = map.idx(x, y);
+            map.tiles[index].kind = TileKind::Ramp;
+            map.tiles[index].ramp_direction = Some(direction);
+        }
+        map
+    }
+
+    fn chunk_meshes(map: &TileMap) -> Vec<Mesh> {
+        terrain::build_chunk_meshes(map, TopNormals::Flat)
+            .into_iter()
+            .map(|(_, mesh)| mesh)
+            .collect()
+    }
+
+    fn triangles(mesh: &Mesh) -> Vec<[Vec3; 3]> {
+        let positions = extract_vec3(mesh, Mesh::ATTRIBUTE_POSITION, "POSITION").unwrap();
+        extract_indices(mesh)
+            .unwrap()
+            .chunks_exact(3)
+            .map(|triangle| {
+                [0, 1, 2].map(|corner| Vec3::from(positions[triangle[corner] as usize]))
+            })
+            .collect()
+    }
+
+    /// Total surface area and area projected onto the ground plane.
+    fn areas(meshes: &[Mesh]) -> (f32, f32) {
+        let mut total = 0.0;
+        let mut projected = 0.0;
+        for [a, b, c] in meshes.iter().flat_map(triangles) {
+            let cross = (b - a).cross(c - a);
+            total += cross.length() / 2.0;
+            projected += cross.y.abs() / 2.0;
+        }
+        (total, projected)
+    }
+
+    /// Positions of vertices that lie inside another triangle's edge.
+    fn t_junctions(meshes: &[Mesh]) -> HashSet<[i64; 3]> {
+        let all: Vec<[Vec3; 3]> = meshes.iter().flat_map(triangles).collect();
+        let mut points = GridPoints::default();
+        for &position in all.iter().flatten() {
+            points.insert(position);
+        }
+        let mut found = HashSet::new();
+        for triangle in &all {
+            for index in 0..3 {
+                for (_, point) in points.on_edge(triangle[index], triangle[(index + 1) % 3]) {
+                    found.insert(
+                        point
+                            .to_array()
+                            .map(|value| (value * KEY_STEPS).round() as i64),
+                    );
+                }
+            }
+        }
+        found
+    }
+
+    #[test]
+    fn flat_map_collapses_to_two_triangles_per_chunk() {
+        let map = TileMap::new(CHUNK_SIZE * 2 + 6, CHUNK_SIZE + 8);
+        let merged = merge_faces(&chunk_meshes(&map)).unwrap();
+        assert_eq!(merged.len(), 6);
+        for mesh in &merged {
+            assert_eq!(triangle_count(mesh), 2);
+        }
+    }
+
+    #[test]
+    fn merging_keeps_area_without_new_t_junctions() {
+        let source = chunk_meshes(&sample_map());
+        let merged = merge_faces(&source).unwrap();
+
+        let source_triangles: u32 = source.iter().map(triangle_count).sum();
+        let merged_triangles: u32 = merged.iter().map(triangle_count).sum();
+        assert!(merged_triangles * 4 < source_triangles);
+
+        let (source_total, source_projected) = areas(&source);
+        let (merged_total, merged_projected) = areas(&merged);
+        assert!((source_total - merged_total).abs() < source_total * 1e-5);
+        assert!((source_projected - merged_projected).abs() < source_projected * 1e-5);
+
+        let before = t_junctions(&source);
+        let after = t_junctions(&merged);
+        assert!(after.is_subset(&before), "{:?}", after.difference(&before));
+    }
+
+    #[test]
+    fn lod_map_flattens_blocks() {
+        // Elevation and type per tile, row by row; the last column is a partial block.
+        let rows = [
+            [
+                (1, TileType::Sand),
+                (1, TileType::Rock),
+                (0, TileType::Grass),
+                (0, TileType::Grass),
+                (3, TileType::Dirt),
+            ],
+            [
+                (0, TileType::Grass),
+                (2, TileType::Dirt),
+                (1, TileType::Rock),
+                (1, TileType::Dirt),
+                (3, TileType::Dirt),
+            ],
+            [
+                (2, TileType::Rock),
+                (2, TileType::Rock),
+                (2, TileType::Rock),
+                (2, TileType::Sand),
+                (0, TileType::Grass),
+            ],
+            [
+                (2, TileType::Sand),
+                (1, TileType::Grass),
+                (2, TileType::Sand),
+                (2, TileType::Sand),
+                (0, TileType::Sand),
+            ],
+        ];
+        let mut map = TileMap::new(5, 4);
+        for (y, row) in rows.iter().enumerate() {
+            for (x, &(elevation, tile_type)) in row.iter().enumerate() {
+                set_tile(&mut map, x as u32, y as u32, elevation, tile_type);
+            }
+        }
+        let ramp = map.idx(3, 1);
+        map.tiles[ramp].kind = TileKind::Ramp;
+        map.tiles[ramp].ramp_direction = Some(RampDirection::West);
+
+        let flattened = |lod: &TileMap| -> Vec<(i8, TileType)> {
+            lod.tiles
+                .iter()
+                .map(|tile| (tile.elevation, tile.tile_type))
+                .collect()
+        };
+        let block = |elevation, tile_type, x: u32, y: u32, size: u32, lod: &TileMap| {
+            for (tx, ty) in (TileRect {
+                x0: x,
+                y0: y,
+                x1: (x + size).min(5),
+                y1: (y + size).min(4),
+            })
+            .tiles()
+            {
+                let tile = lod.get(tx, ty);
+                assert_eq!(
+                    (tile.elevation, tile.tile_type),
+                    (elevation, tile_type),
+                    "tile ({tx}, {ty})"
+                );
+                assert_eq!(tile.kind, TileKind::Floor);
+                assert_eq!(tile.ramp_direction, None);
+            }
+        };
+
+        let lod = lod_map(&map, 1);
+        // Two tiles at elevation 1 beat one each at 0 and 2; Sand and Rock tie there
+        // and the lower type index wins.
+        block(1, TileType::Sand, 0, 0, 2, &lod);
+        // Elevations 0 and 1 tie and the higher one wins; Rock and Dirt tie there
+        // and Dirt, the lower type index, wins.
+        block(1, TileType::Dirt, 2, 0, 2, &lod);
+        block(3, TileType::Dirt, 4, 0, 2, &lod);
+        block(2, TileType::Rock, 0, 2, 2, &lod);
+        block(2, TileType::Sand, 2, 2, 2, &lod);
+        block(0, TileType::Grass, 4, 2, 2, &lod);
+
+        let lod = lod_map(&map, 2);
+        // Seven tiles at elevation 2, four of them Sand.
+        block(2, TileType::Sand, 0, 0, 4, &lod);
+        block(3, TileType::Dirt, 4, 0, 4, &lod);
+        assert_ne!(flattened(&lod), flattened(&map));
+        assert_eq!(flattened(&lod_map(&map, 0)).len(), 20);
+    }
+}
@@ -0,0 +1,122 @@
+use crate::types::{RampDirection, TileKind, TileMap, TileType};
+
+/// Largest map edge the New Map dialog accepts.
+pub const MAX_MAP_SIZE: u32 = 1024;
+/// Highest elevation step the editor can paint.
+pub const MAX_ELEVATION: i8 = 3;
+/// Spacing in tiles between the random lattice points of the hills template.
+const HILL_SPACING: u32 = 12;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum MapTemplate {
+    /// Every tile uses the default type and elevation.
+    Blank,
+    /// Rolling plateaus above the default elevation, with a few ramps between levels.
+    RandomHills { seed: u64 },
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct NewMapSettings {
+    pub width: u32,
+    pub height: u32,
+    pub tile_type: TileType,
+    pub elevation: i8,
+    pub template: MapTemplate,
+}
+
+impl Default for NewMapSettings {
+    fn default() -> Self {
+        Self {
+            width: 64,
+            height: 64,
+            tile_type: TileType::default(),
+            elevation: 0,
+            template: MapTemplate::Blank,
+        }
+    }
+}
+
+pub fn generate_map(settings: &NewMapSettings) -> TileMap {
+    let width = settings.width.clamp(1, MAX_MAP_SIZE);
+    let height = settings.height.clamp(1, MAX_MAP_SIZE);
+    let base = settings.elevation.min(MAX_ELEVATION);
+
+    let mut map = TileMap::new(width, height);
+    for tile in &mut map.tiles {
+        tile.tile_type = settings.tile_type;
+        tile.elevation = base;
+    }
+
+    if let MapTemplate::RandomHills { seed } = settings.template {
+        raise_hills(&mut map, seed, settings.tile_type, base);
+        place_ramps(&mut map, seed);
+    }
+
+    map
+}
+
+fn raise_hills(map: &mut TileMap, seed: u64, tile_type: TileType, base: i8) {
+    let levels = (MAX_ELEVATION - base).max(0) as f32;
+    for y in 0..map.height {
+        for x in 0..map.width {
+            let noise = value_noise(seed, x, y);
+            // Bias towards the base level so most of the map stays open ground.
+            let level = ((noise * noise) * (levels + 1.0)).floor().min(levels) as i8;
+            let index = map.idx(x, y);
+            let tile = &mut map.tiles[index];
+            tile.elevation = base + level;
+            if level > 0 {
+                tile.tile_type = TileType::ALL[(tile_type.as_index() + level as usize) % 4];
+            }
+        }
+    }
+}
+
+/// Turns a sparse selection of plateau edge tiles into ramps down to the level below.
+fn place_ramps(map: &mut TileMap, seed: u64) {
+    for y in 0..map.height {
+        for x in 0..map.width {
+            if !hash(seed ^ 0x5eed, x, y).is_multiple_of(6) {
+                continue;
+            }
+            let elevation = map.get(x, y).elevation;
+            let direction = RampDirection::ALL.into_iter().find(|direction| {
+                let (dx, dy) = direction.offset();
+                let (nx, ny) = (x as i32 + dx, y as i32 + dy);
+                nx >= 0
+                    && ny >= 0
+                    && (nx as u32) < map.width
+                    && (ny as u32) < map.height
+                    && map.get(nx as u32, ny as u32).elevation == elevation - 1
+            });
+            if let Some(direction) = direction {
+                let index = map.idx(x, y);
+                map.tiles[index].kind = TileKind::Ramp;
+                map.tiles[index].ramp_direction = Some(direction);
+            }
+        }
+    }
+}
+
+/// Smoothly interpolated lattice noise in `0.0..1.0`.
+fn value_noise(seed: u64, x: u32, y: u32) -> f32 {
+    let (cx, cy) = (x / HILL_SPACING, y / HILL_SPACING);
+    let fx = (x % HILL_SPACING) as f32 / HILL_SPACING as f32;
+    let fy = (y % HILL_SPACING) as f32 / HILL_SPACING as f32;
+    let corner =
+        |dx: u32, dy: u32| (hash(seed, cx + dx, cy + dy) >> 40) as f32 / (1u64 << 24) as f32;
+
+    let smooth = |t: f32| t * t * (3.0 - 2.0 * t);
+    let (sx, sy) = (smooth(fx), smooth(fy));
+    let top = corner(0, 0) + (corner(1, 0) - corner(0, 0)) * sx;
+    let bottom = corner(0, 1) + (corner(1, 1) - corner(0, 1)) * sx;
+    top + (bottom - top) * sy
+}
+
+/// SplitMix64 over the seed and coordinates; stable across platforms and runs.
+fn hash(seed: u64, x: u32, y: u32) -> u64 {
+    let mut z = seed ^ ((x as u64) << 32 | y as u64).wrapping_mul(0x9E37_79B9_7F4A_7C15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    z ^ (z >> 31)
+}
@@ -0,0 +1,221 @@
+use std::path::Path;
+
+use anyhow::{Context, Result, ensure};
+use image::imageops::{self, FilterType};
+use image::{ImageBuffer, Luma, RgbImage};
+
+use crate::generate::MAX_ELEVATION;
+use crate::types::{RampDirection, TileKind, TileMap, TileType};
+
+/// Maps a colour in the tile-type image to a tile type.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct PaletteEntry {
+    pub color: [u8; 3],
+    pub tile_type: TileType,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct HeightmapImportSettings {
+    /// Normalised brightness (0..1) at which each elevation step above `base_elevation`
+    /// begins, in ascending order.
+    pub thresholds: Vec<f32>,
+    /// Elevation of the darkest tiles. With the thresholds it may not exceed
+    /// `MAX_ELEVATION`.
+    pub base_elevation: i8,
+    /// Colours of the optional tile-type image. Pixels use the nearest entry.
+    pub palette: Vec<PaletteEntry>,
+    pub auto_ramps: bool,
+    /// Largest brightness difference between two tiles one step apart that still counts
+    /// as a slope (and gets a ramp) rather than a cliff.
+    pub ramp_max_drop: f32,
+}
+
+impl Default for HeightmapImportSettings {
+    fn default() -> Self {
+        Self {
+            thresholds: vec![0.25, 0.5, 0.75],
+            base_elevation: 0,
+            palette: vec![
+                PaletteEntry {
+                    color: [60, 160, 50],
+                    tile_type: TileType::Grass,
+                },
+                PaletteEntry {
+                    color: [130, 90, 50],
+                    tile_type: TileType::Dirt,
+                },
+                PaletteEntry {
+                    color: [230, 200, 120],
+                    tile_type: TileType::Sand,
+                },
+                PaletteEntry {
+                    color: [128, 128, 128],
+                    tile_type: TileType::Rock,
+                },
+            ],
+            auto_ramps: false,
+            ramp_max_drop: 0.2,
+        }
+    }
+}
+
+/// Replaces the elevations (and, with `tile_types`, the tile types) of `map` from images.
+///
+/// Both images are resampled to the map size. Existing ramps are cleared; with
+/// `auto_ramps` new ones are placed where the heightmap descends a single step gently.
+pub fn import_heightmap(
+    map: &mut TileMap,
+    heightmap: &Path,
+    tile_types: Option<&Path>,
+    settings: &HeightmapImportSettings,
+) -> Result<()> {
+    ensure!(
+        map.width > 0 && map.height > 0,
+        "Cannot import a heightmap into an empty map"
+    );
+    ensure!(
+        settings
+            .thresholds
+            .windows(2)
+            .all(|pair| pair[0] <= pair[1]),
+        "Heightmap thresholds must be in ascending order"
+    );
+    let highest = settings.base_elevation as usize + settings.thresholds.len();
+    ensure!(
+        (0..=MAX_ELEVATION).contains(&settings.base_elevation) && highest <= MAX_ELEVATION as usize,
+        "Base elevation {} with {} steps reaches elevation {highest}, above the editor's {MAX_ELEVATION}",
+        settings.base_elevation,
+        settings.thresholds.len()
+    );
+
+    let heights = load_heights(heightmap, map.width, map.height)?;
+    let types = tile_types
+        .map(|path| load_tile_types(path, map.width, map.height, &settings.palette))
+        .transpose()?;
+
+    for (index, tile) in map.tiles.iter_mut().enumerate() {
+        let value = heights[index];
+        let steps = settings
+            .thresholds
+            .iter()
+            .filter(|threshold| value >= **threshold)
+            .count();
+        tile.elevation = settings.base_elevation + steps as i8;
+        tile.kind = TileKind::Floor;
+        tile.ramp_direction = None;
+        if let Some(types) = types.as_ref() {
+            tile.tile_type = types[index];
+        }
+    }
+
+    if settings.auto_ramps {
+        place_ramps(map, &heights, settings.ramp_max_drop);
+    }
+    Ok(())
+}
+
+/// Brightness of every tile in `0.0..=1.0`, row-major.
+fn load_heights(path: &Path, width: u32, height: u32) -> Result<Vec<f32>> {
+    let image = image::open(path)
+        .with_context(|| format!("Failed to read heightmap {}", path.display()))?
+        .into_luma16();
+    let resized: ImageBuffer<Luma<u16>, Vec<u16>> =
+        imageops::resize(&image, width, height, FilterType::Triangle);
+    Ok(resized
+        .pixels()
+        .map(|pixel| pixel.0[0] as f32 / u16::MAX as f32)
+        .collect())
+}
+
+fn load_tile_types(
+    path: &Path,
+    width: u32,
+    height: u32,
+    palette: &[PaletteEntry],
+) -> Result<Vec<TileType>> {
+    ensure!(!palette.is_empty(), "The tile type palette is empty");
+    let image = image::open(path)
+        .with_context(|| format!("Failed to read tile type image {}", path.display()))?
+        .into_rgb8();
+    // Nearest keeps palette colours intact instead of blending them into new ones.
+    let resized: RgbImage = imageops::resize(&image, width, height, FilterType::Nearest);
+    Ok(resized
+        .pixels()
+        .map(|pixel| nearest_palette_entry(pixel.0, palette))
+        .collect())
+}
+
+fn nearest_palette_entry(color: [u8; 3], palette: &[PaletteEntry]) -> TileType {
+    palette
+        .iter()
+        .min_by_key(|entry| {
+            entry
+                .color
+                .iter()
+                .zip(color)
+                .map(|(a, b)| (*a as i32 - b as i32).pow(2))
+                .sum::<i32>()
+        })
+        .map(|entry| entry.tile_type)
+        .unwrap_or_default()
+}
+
+/// Places ramps on gentle one-step descents, but keeps them sparse: the gentlest
+/// candidates go first, each lower tile receives at most one ramp, and a ramp is
+/// never placed next to another, so a long slope gets a few ramps instead of a
+/// continuous row.
+fn place_ramps(map: &mut TileMap, heights: &[f32], max_drop: f32) {
+    let mut candidates = Vec::new();
+    for y in 0..map.height {
+        for x in 0..map.width {
+            let index = map.idx(x, y);
+            let elevation = map.tiles[index].elevation;
+
+            // Prefer the gentlest descent onto a neighbour exactly one step lower.
+            let mut best: Option<(RampDirection, usize, f32)> = None;
+            for direction in RampDirection::ALL {
+                let (dx, dy) = direction.offset();
+                let (nx, ny) = (x as i32 + dx, y as i32 + dy);
+                if nx < 0 || ny < 0 || nx as u32 >= map.width || ny as u32 >= map.height {
+                    continue;
+                }
+                let neighbor = map.idx(nx as u32, ny as u32);
+                if map.tiles[neighbor].elevation != elevation - 1 {
+                    continue;
+                }
+                let drop = heights[index] - heights[neighbor];
+                if drop <= max_drop && best.is_none_or(|(_, _, current)| drop < current) {
+                    best = Some((direction, neighbor, drop));
+                }
+            }
+
+            if let Some((direction, lower, drop)) = best {
+                candidates.push((x, y, direction, lower, drop));
+            }
+        }
+    }
+    candidates.sort_by(|a, b| a.4.total_cmp(&b.4));
+
+    let mut is_ramp = vec![false; map.tiles.len()];
+    let mut is_landing = vec![false; map.tiles.len()];
+    for (x, y, direction, lower, _) in candidates {
+        let index = map.idx(x, y);
+        let crowded = (-1..=1).any(|dy: i32| {
+            (-1..=1).any(|dx: i32| {
+                let (nx, ny) = (x as i32 + dx, y as i32 + dy);
+                nx >= 0
+                    && ny >= 0
+                    && (nx as u32) < map.width
+                    && (ny as u32) < map.height
+                    && is_ramp[map.idx(nx as u32, ny as u32)]
+            })
+        });
+        if crowded || is_landing[index] || is_landing[lower] || is_ramp[lower] {
+            continue;
+        }
+        is_ramp[index] = true;
+        is_landing[lower] = true;
+        map.tiles[index].kind = TileKind::Ramp;
+        map.tiles[index].ramp_direction = Some(direction);
+    }
+}
@@ -0,0 +1,344 @@
+//! Compact structure-of-arrays map encoding used by binary format version 2.
+//!
+//! Each tile field is stored as its own plane of bytes and run-length encoded,
+//! tile coordinates are implied by the row-major index, and the whole payload is
+//! deflated. Large maps are dominated by long runs of identical values, so this
+//! is far smaller than bincode's per-tile structs.
+
+use std::io::{Read, Write};
+
+use anyhow::{Result, bail, ensure};
+use flate2::Compression;
+use flate2::read::DeflateDecoder;
+use flate2::write::DeflateEncoder;
+
+use crate::generate::MAX_MAP_SIZE;
+use crate::types::{
+    RampDirection, Tile, TileKind, TileMap, TileType, WalkOverride, WalkOverrideCell,
+};
+
+/// Most tiles a decoded map may have: four times the largest generated map, which
+/// leaves room for imported maps while bounding what a corrupt header can allocate.
+const MAX_TILES: usize = 4 * (MAX_MAP_SIZE as usize) * (MAX_MAP_SIZE as usize);
+
+pub fn encode(map: &TileMap) -> Result<Vec<u8>> {
+    let mut raw = Vec::new();
+    write_varint(&mut raw, map.width as u64);
+    write_varint(&mut raw, map.height as u64);
+
+    write_plane(&mut raw, map.tiles.iter().map(|tile| kind_code(tile.kind)));
+    write_plane(
+        &mut raw,
+        map.tiles.iter().map(|tile| tile.tile_type.as_index() as u8),
+    );
+    write_plane(&mut raw, map.tiles.iter().map(|tile| tile.elevation as u8));
+    write_plane(
+        &mut raw,
+        map.tiles.iter().map(|tile| ramp_code(tile.ramp_direction)),
+    );
+
+    write_varint(&mut raw, map.walk_overrides.len() as u64);
+    for cell in &map.walk_overrides {
+        write_varint(&mut raw, cell.x as u64);
+        write_varint(&mut raw, cell.y as u64);
+        raw.push(match cell.mode {
+            WalkOverride::Blocked => 0,
+            WalkOverride::Open => 1,
+        });
+    }
+
+    let mut encoder = DeflateEncoder::new(Vec::new(), Compression::best());
+    encoder.write_all(&raw)?;
+    Ok(encoder.finish()?)
+}
+
+pub fn decode(bytes: &[u8]) -> Result<TileMap> {
+    let mut inflater = DeflateDecoder::new(bytes);
+    let mut next_byte = || {
+        let mut byte = [0u8];
+        match inflater.read_exact(&mut byte) {
+            Ok(()) => Ok(byte[0]),
+            Err(_) => bail!("Compact map data is truncated"),
+        }
+    };
+    let width = u32::try_from(decode_varint(&mut next_byte)?)?;
+    let height = u32::try_from(decode_varint(&mut next_byte)?)?;
+    let count = (width as usize)
+        .checked_mul(height as usize)
+        .filter(|&count| count <= MAX_TILES)
+        .ok_or_else(|| anyhow::anyhow!("Map dimensions {width}x{height} are too large"))?;
+
+    // Inflate no further than the longest valid encoding of a map this size, so a
+    // small file can't expand without bound.
+    let limit = max_body_len(count);
+    let mut raw = Vec::new();
+    inflater.take(limit as u64 + 1).read_to_end(&mut raw)?;
+    ensure!(
+        raw.len() <= limit,
+        "Compact map data is longer than a {width}x{height} map allows"
+    );
+    let mut reader = Reader { data: &raw, pos: 0 };
+
+    let kinds = reader.plane(count)?;
+    let types = reader.plane(count)?;
+    let elevations = reader.plane(count)?;
+    let ramps = reader.plane(count)?;
+
+    let mut tiles = Vec::with_capacity(kinds.len());
+    for index in 0..count {
+        tiles.push(Tile {
+            kind: kind_from_code(kinds[index])?,
+            tile_type: tile_type_from_code(types[index])?,
+            x: index as u32 % width.max(1),
+            y: index as u32 / width.max(1),
+            elevation: elevations[index] as i8,
+            ramp_direction: ramp_from_code(ramps[index])?,
+        });
+    }
+
+    let override_count = reader.varint()? as usize;
+    let mut walk_overrides = Vec::with_capacity(override_count.min(count));
+    for _ in 0..override_count {
+        let x = u32::try_from(reader.varint()?)?;
+        let y = u32::try_from(reader.varint()?)?;
+        let mode = match reader.byte()? {
+            0 => WalkOverride::Blocked,
+            1 => WalkOverride::Open,
+            other => bail!("Unknown walkability override {other}"),
+        };
+        walk_overrides.push(WalkOverrideCell { x, y, mode });
+    }
+
+    ensure!(
+        reader.pos == raw.len(),
+        "Compact map data has trailing bytes"
+    );
+
+    Ok(TileMap {
+        width,
+        height,
+        tiles,
+        walk_overrides,
+    })
+}
+
+/// Upper bound on the bytes after the dimensions for a map of `count` tiles. Plane
+/// runs take at most two bytes per tile (runs of one), and each walkability
+/// override at most 11 after a count of at most 10.
+fn max_body_len(count: usize) -> usize {
+    4 * 2 * count + 10 + 11 * count
+}
+
+fn kind_code(kind: TileKind) -> u8 {
+    match kind {
+        TileKind::Floor => 0,
+        TileKind::Ramp => 1,
+    }
+}
+
+fn kind_from_code(code: u8) -> Result<TileKind> {
+    Ok(match code {
+        0 => TileKind::Floor,
+        1 => TileKind::Ramp,
+        other => bail!("Unknown tile kind {other}"),
+    })
+}
+
+fn tile_type_from_code(code: u8) -> Result<TileType> {
+    TileType::ALL
+        .into_iter()
+        .find(|tile_type| tile_type.as_index() == code as usize)
+        .ok_or_else(|| anyhow::anyhow!("Unknown tile type {code}"))
+}
+
+fn ramp_code(direction: Option<RampDirection>) -> u8 {
+    match direction {
+        None => 0,
+        Some(RampDirection::North) => 1,
+        Some(RampDirection::East) => 2,
+        Some(RampDirection::South) => 3,
+        Some(RampDirection::West) => 4,
+    }
+}
+
+fn ramp_from_code(code: u8) -> Result<Option<RampDirection>> {
+    Ok(match code {
+        0 => None,
+        1 => Some(RampDirection::North),
+        2 => Some(RampDirection::East),
+        3 => Some(RampDirection::South),
+        4 => Some(RampDirection::West),
+        other => bail!("Unknown ramp direction {other}"),
+    })
+}
+
+/// Writes a plane as `(run length, value)` pairs.
+fn write_plane(out: &mut Vec<u8>, values: impl Iterator<Item = u8>) {
+    let mut current: Option<(u8, u64)> = None;
+    for value in values {
+        current = match current {
+            Some((run_value, run)) if run_value == value => Some((run_value, run + 1)),
+            Some((run_value, run)) => {
+                write_varint(out, run);
+                out.push(run_value);
+                Some((value, 1))
+            }
+            None => Some((value, 1)),
+        };
+    }
+    if let Some((run_value, run)) = current {
+        write_varint(out, run);
+        out.push(run_value);
+    }
+}
+
+/// Unsigned LEB128.
+fn write_varint(out: &mut Vec<u8>, mut value: u64) {
+    loop {
+        let byte = (value & 0x7f) as u8;
+        value >>= 7;
+        if value == 0 {
+            out.push(byte);
+            return;
+        }
+        out.push(byte | 0x80);
+    }
+}
+
+/// Unsigned LEB128, reading bytes from `next_byte`.
+fn decode_varint(mut next_byte: impl FnMut() -> Result<u8>) -> Result<u64> {
+    let mut value = 0u64;
+    for shift in (0..64).step_by(7) {
+        let byte = next_byte()?;
+        value |= ((byte & 0x7f) as u64) << shift;
+        if byte & 0x80 == 0 {
+            return Ok(value);
+        }
+    }
+    bail!("Compact map varint is too long")
+}
+
+struct Reader<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl Reader<'_> {
+    fn byte(&mut self) -> Result<u8> {
+        let Some(&byte) = self.data.get(self.pos) else {
+            bail!("Compact map data is truncated");
+        };
+        self.pos += 1;
+        Ok(byte)
+    }
+
+    fn varint(&mut self) -> Result<u64> {
+        decode_varint(|| self.byte())
+    }
+
+    fn plane(&mut self, count: usize) -> Result<Vec<u8>> {
+        // `count` comes from the file header, so don't reserve more than the input
+        // size up front; runs that really cover the map grow the plane as read.
+        let mut values = Vec::with_capacity(count.min(self.data.len() - self.pos));
+        while values.len() < count {
+            let run = self.varint()? as usize;
+            let value = self.byte()?;
+            ensure!(
+                run > 0 && run <= count - values.len(),
+                "Compact map plane has an invalid run length"
+            );
+            values.resize(values.len() + run, value);
+        }
+        Ok(values)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bincode::config;
+
+    fn sample_map(size: u32) -> TileMap {
+        let mut map = TileMap::new(size, size);
+        for y in 0..size {
+            for x in 0..size {
+                let index = map.idx(x, y);
+                let tile = &mut map.tiles[index];
+                // Rolling plateaus and texture patches, roughly like a hand-made map.
+                tile.elevation = (((x / 48) + (y / 64)) % 4) as i8;
+                tile.tile_type = TileType::ALL[((x / 32 + y / 40) % 4) as usize];
+                // Sprinkle some hand-painted detail so runs are not perfectly regular.
+                if (x.wrapping_mul(73_856_093) ^ y.wrapping_mul(19_349_663)) % 97 == 0 {
+                    tile.tile_type = TileType::Rock;
+                }
+                if x % 48 == 0 && y % 16 == 3 && tile.elevation > 0 {
+                    tile.kind = TileKind::Ramp;
+                    tile.ramp_direction = Some(RampDirection::West);
+                }
+            }
+        }
+        map.set_walk_override(10, 20, Some(WalkOverride::Blocked));
+        map.set_walk_override(11, 20, Some(WalkOverride::Open));
+        map
+    }
+
+    #[test]
+    fn compact_round_trip_preserves_map() {
+        let map = sample_map(64);
+        let decoded = decode(&encode(&map).unwrap()).unwrap();
+
+        assert_eq!(decoded.width, map.width);
+        assert_eq!(decoded.height, map.height);
+        assert_eq!(decoded.walk_overrides, map.walk_overrides);
+        for (a, b) in map.tiles.iter().zip(&decoded.tiles) {
+            assert_eq!(a.kind, b.kind);
+            assert_eq!(a.tile_type, b.tile_type);
+            assert_eq!(a.elevation, b.elevation);
+            assert_eq!(a.ramp_direction, b.ramp_direction);
+            assert_eq!((a.x, a.y), (b.x, b.y));
+        }
+    }
+
+    #[test]
+    fn compact_encoding_shrinks_large_maps() {
+        let map = sample_map(512);
+        let bincode_size = bincode::encode_to_vec(&map, config::standard())
+            .unwrap()
+            .len();
+        let compact_size = encode(&map).unwrap().len();
+
+        println!(
+            "512x512 map: bincode {bincode_size} bytes, compact {compact_size} bytes ({:.1}x smaller)",
+            bincode_size as f64 / compact_size as f64
+        );
+        assert!(compact_size * 20 < bincode_size);
+    }
+
+    #[test]
+    fn oversized_dimensions_are_rejected() {
+        let mut raw = Vec::new();
+        write_varint(&mut raw, 100_000);
+        write_varint(&mut raw, 100_000);
+        write_plane(&mut raw, std::iter::repeat_n(0, 4));
+        let mut encoder = DeflateEncoder::new(Vec::new(), Compression::best());
+        encoder.write_all(&raw).unwrap();
+
+        let error = decode(&encoder.finish().unwrap()).unwrap_err().to_string();
+        assert!(error.contains("too large"), "{error}");
+    }
+
+    #[test]
+    fn overlong_payload_is_not_inflated() {
+        let mut raw = Vec::new();
+        write_varint(&mut raw, 10);
+        write_varint(&mut raw, 10);
+        raw.resize(raw.len() + (1 << 20), 0);
+        let mut encoder = DeflateEncoder::new(Vec::new(), Compression::best());
+        encoder.write_all(&raw).unwrap();
+        let bytes = encoder.finish().unwrap();
+        assert!(bytes.len() < 4096);
+
+        let error = decode(&bytes).unwrap_err().to_string();
+        assert!(error.contains("longer than"), "{error}");
+    }
+}
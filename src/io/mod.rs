@@ -0,0 +1,407 @@
+use crate::types::{Tile, TileMap};
+use anyhow::{Context, bail, ensure};
+use bincode::{Decode, config, decode_from_slice};
+use std::path::Path;
+
+mod compact;
+
+const KEY: u8 = 0xAA;
+
+/// Magic bytes at the start of every map file written by the editor.
+const MAGIC: &[u8; 4] = b"TMAP";
+/// Format version written by `save_map`. Bump this and add a step to
+/// [`VersionedMap::upgrade`] whenever the encoded `TileMap` layout changes.
+///
+/// Versions 0 and 1 store the map as plain bincode; version 2 switched to the
+/// compressed per-field encoding in [`compact`] without changing `TileMap`.
+pub const CURRENT_FORMAT_VERSION: u16 = 2;
+/// magic (4) + version (2) + flags (2) + payload length (4) + CRC-32 (4)
+const HEADER_LEN: usize = 16;
+
+fn obfuscate(data: &mut [u8]) {
+    for b in data.iter_mut() {
+        *b ^= KEY;
+    }
+}
+
+// Version 0: the layout written before walkability overrides were added to `TileMap`.
+#[derive(Decode)]
+struct TileMapV0 {
+    width: u32,
+    height: u32,
+    tiles: Vec<Tile>,
+}
+
+impl From<TileMapV0> for TileMap {
+    fn from(legacy: TileMapV0) -> Self {
+        TileMap {
+            width: legacy.width,
+            height: legacy.height,
+            tiles: legacy.tiles,
+            walk_overrides: Vec::new(),
+        }
+    }
+}
+
+/// A decoded map at the version it was stored with.
+enum VersionedMap {
+    V0(TileMapV0),
+    V1(TileMap),
+}
+
+impl VersionedMap {
+    /// Runs the migration chain one version at a time until the current layout is reached.
+    fn upgrade(self) -> TileMap {
+        let mut map = self;
+        loop {
+            map = match map {
+                VersionedMap::V0(v0) => VersionedMap::V1(v0.into()),
+                VersionedMap::V1(current) => return current,
+            };
+        }
+    }
+}
+
+/// On-disk representation of a map, chosen from the file extension when saving.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MapFormat {
+    /// Versioned, compressed and obfuscated binary (any extension other than the text ones).
+    Binary,
+    /// Human-readable JSON with one tile row per line.
+    Json,
+    /// Human-readable RON with one tile row per line.
+    Ron,
+}
+
+impl MapFormat {
+    pub fn from_path(path: &Path) -> Self {
+        match path
+            .extension()
+            .and_then(|ext| ext.to_str())
+            .map(|ext| ext.to_ascii_lowercase())
+            .as_deref()
+        {
+            Some("json") => MapFormat::Json,
+            Some("ron") => MapFormat::Ron,
+            _ => MapFormat::Binary,
+        }
+    }
+}
+
+pub fn save_map(path: impl AsRef<Path>, map: &TileMap) -> anyhow::Result<()> {
+    let path = path.as_ref();
+    let bytes = match MapFormat::from_path(path) {
+        MapFormat::Binary => encode_binary(map)?,
+        MapFormat::Json => encode_json(map)?.into_bytes(),
+        MapFormat::Ron => encode_ron(map)?.into_bytes(),
+    };
+    std::fs::write(path, bytes)
+        .with_context(|| format!("Failed to write map {}", path.display()))?;
+    Ok(())
+}
+
+fn encode_binary(map: &TileMap) -> anyhow::Result<Vec<u8>> {
+    let mut payload = compact::encode(map)?;
+    obfuscate(&mut payload);
+
+    let mut bytes = Vec::with_capacity(HEADER_LEN + payload.len());
+    bytes.extend_from_slice(MAGIC);
+    bytes.extend_from_slice(&CURRENT_FORMAT_VERSION.to_le_bytes());
+    bytes.extend_from_slice(&0u16.to_le_bytes());
+    bytes.extend_from_slice(&(payload.len() as u32).to_le_bytes());
+    bytes.extend_from_slice(&crc32fast::hash(&payload).to_le_bytes());
+    bytes.extend_from_slice(&payload);
+    Ok(bytes)
+}
+
+// The text encoders lay the tile list out one map row per line so that editing a
+// tile only touches the line of its row in a diff. The result is still a plain
+// serialisation of `TileMap`, so it loads through serde like any other document.
+
+fn encode_json(map: &TileMap) -> anyhow::Result<String> {
+    let mut out = String::new();
+    out.push_str("{\n");
+    out.push_str(&format!("  \"width\": {},\n", map.width));
+    out.push_str(&format!("  \"height\": {},\n", map.height));
+    out.push_str("  \"tiles\": [");
+    let rows = tile_rows(map);
+    for (index, row) in rows.iter().enumerate() {
+        let cells = row
+            .iter()
+            .map(serde_json::to_string)
+            .collect::<Result<Vec<_>, _>>()?;
+        out.push_str("\n    ");
+        out.push_str(&cells.join(", "));
+        if index + 1 < rows.len() {
+            out.push(',');
+        }
+    }
+    out.push_str(if rows.is_empty() { "],\n" } else { "\n  ],\n" });
+    out.push_str("  \"walk_overrides\": ");
+    out.push_str(&serde_json::to_string(&map.walk_overrides)?);
+    out.push_str("\n}\n");
+    Ok(out)
+}
+
+fn encode_ron(map: &TileMap) -> anyhow::Result<String> {
+    let mut out = String::new();
+    out.push_str("(\n");
+    out.push_str(&format!("    width: {},\n", map.width));
+    out.push_str(&format!("    height: {},\n", map.height));
+    out.push_str("    tiles: [");
+    for row in tile_rows(map) {
+        let cells = row
+            .iter()
+            .map(ron::to_string)
+            .collect::<Result<Vec<_>, _>>()?;
+        out.push_str("\n        ");
+        out.push_str(&cells.join(", "));
+        out.push(',');
+    }
+    out.push_str("\n    ],\n");
+    out.push_str("    walk_overrides: ");
+    out.push_str(&ron::to_string(&map.walk_overrides)?);
+    out.push_str(",\n)\n");
+    Ok(out)
+}
+
+fn tile_rows(map: &TileMap) -> Vec<&[Tile]> {
+    if map.width == 0 {
+        return Vec::new();
+    }
+    map.tiles.chunks(map.width as usize).collect()
+}
+
+pub fn load_map(path: impl AsRef<Path>) -> anyhow::Result<TileMap> {
+    let path = path.as_ref();
+    let bytes =
+        std::fs::read(path).with_context(|| format!("Failed to read map {}", path.display()))?;
+    decode_map(&bytes).with_context(|| format!("Failed to load map {}", path.display()))
+}
+
+pub fn decode_map(bytes: &[u8]) -> anyhow::Result<TileMap> {
+    if bytes.starts_with(MAGIC) {
+        return decode_binary(bytes).and_then(ensure_consistent);
+    }
+
+    // Sniff the content rather than trusting the extension: older saves used
+    // `.json` names for binary data, so fall back to the legacy decoder when
+    // something that looks like text fails to parse.
+    let text = match bytes.iter().find(|b| !b.is_ascii_whitespace()) {
+        Some(b'{') => Some(serde_json::from_slice::<TileMap>(bytes).context("Invalid JSON map")),
+        Some(b'(') => Some(ron::de::from_bytes::<TileMap>(bytes).context("Invalid RON map")),
+        _ => None,
+    };
+
+    match text {
+        Some(Ok(map)) => ensure_consistent(map),
+        Some(Err(err)) => decode_headerless(bytes).map_err(|_| err),
+        None => decode_headerless(bytes),
+    }
+}
+
+fn ensure_consistent(map: TileMap) -> anyhow::Result<TileMap> {
+    ensure!(
+        map.tiles.len() == (map.width as usize) * (map.height as usize),
+        "Map data is inconsistent ({} tiles for a {}x{} map)",
+        map.tiles.len(),
+        map.width,
+        map.height
+    );
+    Ok(map)
+}
+
+fn decode_binary(bytes: &[u8]) -> anyhow::Result<TileMap> {
+    ensure!(bytes.len() >= HEADER_LEN, "Map file header is truncated");
+    let version = u16::from_le_bytes([bytes[4], bytes[5]]);
+    let payload_len = u32::from_le_bytes(bytes[8..12].try_into()?) as usize;
+    let checksum = u32::from_le_bytes(bytes[12..16].try_into()?);
+
+    if version > CURRENT_FORMAT_VERSION {
+        bail!(
+            "Map was saved with format version {version}, but this editor only supports up to \
+             version {CURRENT_FORMAT_VERSION}"
+        );
+    }
+
+    let payload = &bytes[HEADER_LEN..];
+    ensure!(
+        payload.len() == payload_len,
+        "Map file is truncated or corrupted (expected {payload_len} bytes of data, found {})",
+        payload.len()
+    );
+    ensure!(
+        crc32fast::hash(payload) == checksum,
+        "Map file is corrupted (checksum mismatch)"
+    );
+
+    let mut payload = payload.to_vec();
+    obfuscate(&mut payload);
+    let versioned = match version {
+        0 => VersionedMap::V0(decode_exact(&payload)?),
+        1 => VersionedMap::V1(decode_exact(&payload)?),
+        _ => VersionedMap::V1(compact::decode(&payload)?),
+    };
+    Ok(versioned.upgrade())
+}
+
+/// Maps saved before the header existed are raw obfuscated bincode. They are
+/// either version 0 or version 1 layouts, so try the newest first.
+fn decode_headerless(bytes: &[u8]) -> anyhow::Result<TileMap> {
+    let mut payload = bytes.to_vec();
+    obfuscate(&mut payload);
+
+    // Arbitrary bytes can occasionally decode, so also require a consistent tile count.
+    if let Ok(map) = decode_exact::<TileMap>(&payload)
+        && map.tiles.len() == (map.width as usize) * (map.height as usize)
+    {
+        return Ok(VersionedMap::V1(map).upgrade());
+    }
+    if let Ok(map) = decode_exact::<TileMapV0>(&payload)
+        && map.tiles.len() == (map.width as usize) * (map.height as usize)
+    {
+        return Ok(VersionedMap::V0(map).upgrade());
+    }
+
+    bail!("Not a tile map file (missing header and not a recognised legacy map)")
+}
+
+fn decode_exact<T: Decode<()>>(payload: &[u8]) -> anyhow::Result<T> {
+    let (value, len) = decode_from_slice(payload, config::standard())?;
+    ensure!(len == payload.len(), "Map data has trailing bytes");
+    Ok(value)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::{RampDirection, TileKind, TileType, WalkOverride};
+    use bincode::encode_to_vec;
+
+    fn sample_map() -> TileMap {
+        let mut map = TileMap::new(5, 3);
+        let index = map.idx(2, 1);
+        map.tiles[index].elevation = 2;
+        map.tiles[index].tile_type = TileType::Rock;
+        let index = map.idx(3, 1);
+        map.tiles[index].kind = TileKind::Ramp;
+        map.tiles[index].elevation = 1;
+        map.tiles[index].ramp_direction = Some(RampDirection::West);
+        map
+    }
+
+    fn assert_same_tiles(a: &TileMap, b: &TileMap) {
+        assert_eq!((a.width, a.height), (b.width, b.height));
+        assert_eq!(a.tiles.len(), b.tiles.len());
+        for (a, b) in a.tiles.iter().zip(&b.tiles) {
+            assert_eq!(a.kind, b.kind);
+            assert_eq!(a.tile_type, b.tile_type);
+            assert_eq!(a.elevation, b.elevation);
+            assert_eq!(a.ramp_direction, b.ramp_direction);
+            assert_eq!((a.x, a.y), (b.x, b.y));
+        }
+    }
+
+    /// Version 0 bincode: `TileMap` without `walk_overrides`.
+    fn encode_v0(map: &TileMap) -> Vec<u8> {
+        encode_to_vec((map.width, map.height, &map.tiles), config::standard()).unwrap()
+    }
+
+    fn encode_v1(map: &TileMap) -> Vec<u8> {
+        encode_to_vec(map, config::standard()).unwrap()
+    }
+
+    fn headerless(mut payload: Vec<u8>) -> Vec<u8> {
+        obfuscate(&mut payload);
+        payload
+    }
+
+    fn with_header(version: u16, mut payload: Vec<u8>) -> Vec<u8> {
+        obfuscate(&mut payload);
+        let mut bytes = Vec::new();
+        bytes.extend_from_slice(MAGIC);
+        bytes.extend_from_slice(&version.to_le_bytes());
+        bytes.extend_from_slice(&0u16.to_le_bytes());
+        bytes.extend_from_slice(&(payload.len() as u32).to_le_bytes());
+        bytes.extend_from_slice(&crc32fast::hash(&payload).to_le_bytes());
+        bytes.extend_from_slice(&payload);
+        bytes
+    }
+
+    #[test]
+    fn binary_round_trip_preserves_map() {
+        let mut map = sample_map();
+        map.set_walk_override(1, 2, Some(WalkOverride::Blocked));
+        let decoded = decode_map(&encode_binary(&map).unwrap()).unwrap();
+        assert_same_tiles(&map, &decoded);
+        assert_eq!(decoded.walk_overrides, map.walk_overrides);
+    }
+
+    #[test]
+    fn text_round_trips_preserve_map() {
+        let mut map = sample_map();
+        map.set_walk_override(4, 0, Some(WalkOverride::Open));
+        for text in [encode_json(&map).unwrap(), encode_ron(&map).unwrap()] {
+            let decoded = decode_map(text.as_bytes()).unwrap();
+            assert_same_tiles(&map, &decoded);
+            assert_eq!(decoded.walk_overrides, map.walk_overrides);
+        }
+    }
+
+    #[test]
+    fn headerless_legacy_maps_load() {
+        let map = sample_map();
+        let v0 = decode_map(&headerless(encode_v0(&map))).unwrap();
+        assert_same_tiles(&map, &v0);
+        assert!(v0.walk_overrides.is_empty());
+
+        let mut with_overrides = sample_map();
+        with_overrides.set_walk_override(0, 0, Some(WalkOverride::Blocked));
+        let v1 = decode_map(&headerless(encode_v1(&with_overrides))).unwrap();
+        assert_same_tiles(&with_overrides, &v1);
+        assert_eq!(v1.walk_overrides, with_overrides.walk_overrides);
+    }
+
+    #[test]
+    fn every_header_version_loads() {
+        let map = sample_map();
+        let v0 = with_header(0, encode_v0(&map));
+        let v1 = with_header(1, encode_v1(&map));
+        let v2 = with_header(2, compact::encode(&map).unwrap());
+        for bytes in [v0, v1, v2] {
+            assert_same_tiles(&map, &decode_map(&bytes).unwrap());
+        }
+    }
+
+    #[test]
+    fn checksum_mismatch_is_rejected() {
+        let mut bytes = encode_binary(&sample_map()).unwrap();
+        let last = bytes.len() - 1;
+        bytes[last] ^= 0xFF;
+        let error = decode_map(&bytes).unwrap_err().to_string();
+        assert!(error.contains("checksum"), "{error}");
+    }
+
+    #[test]
+    fn truncated_payload_is_rejected() {
+        let mut bytes = encode_binary(&sample_map()).unwrap();
+        bytes.pop();
+        let error = decode_map(&bytes).unwrap_err().to_string();
+        assert!(error.contains("truncated"), "{error}");
+
+        let error = decode_map(&bytes[..HEADER_LEN - 1])
+            .unwrap_err()
+            .to_string();
+        assert!(error.contains("truncated"), "{error}");
+    }
+
+    #[test]
+    fn future_version_is_rejected() {
+        let bytes = with_header(
+            CURRENT_FORMAT_VERSION + 1,
+            compact::encode(&sample_map()).unwrap(),
+        );
+        let error = decode_map(&bytes).unwrap_err().to_string();
+        assert!(error.contains("format version"), "{error}");
+    }
+}
@@ -0,0 +1,39 @@
+//! Tile map data, file IO, terrain meshing and map packaging for Tilemap Edit 3D.
+//!
+//! Games can depend on this crate with `default-features = false` to load maps and
+//! packages, query terrain heights and build meshes without pulling in the editor.
+//! The `editor` feature adds the egui/rfd editor application modules used by the
+//! `dprmapedit` binary.
+
+pub mod export;
+pub mod generate;
+pub mod heightmap;
+pub mod io;
+pub mod package;
+pub mod pathing;
+pub mod query;
+pub mod raster;
+pub mod terrain;
+pub mod texture;
+pub mod tiled;
+pub mod types;
+pub mod validation;
+
+#[cfg(feature = "editor")]
+pub mod autosave;
+#[cfg(feature = "editor")]
+pub mod camera;
+#[cfg(feature = "editor")]
+pub mod controls;
+#[cfg(feature = "editor")]
+pub mod debug;
+#[cfg(feature = "editor")]
+pub mod editor;
+#[cfg(feature = "editor")]
+pub mod grid_visual;
+#[cfg(feature = "editor")]
+pub mod runtime;
+#[cfg(feature = "editor")]
+pub mod ui;
+#[cfg(feature = "editor")]
+pub mod walkability_visual;
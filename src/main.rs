@@ -1,30 +1,36 @@
-mod camera;
-mod controls;
-mod debug;
-mod editor;
-mod export;
-mod grid_visual;
-mod io;
-mod runtime;
-mod terrain;
-mod texture;
-mod types;
-mod ui;
+mod cli;
 
-use crate::debug::asset::image_inspector::ImageInspectorPlugin;
-use crate::texture::material;
 use bevy::prelude::*;
 use bevy_egui::EguiPlugin;
-use camera::CameraPlugin;
-use controls::ControlsPlugin;
-use editor::EditorPlugin;
-use runtime::RuntimePlugin;
-use texture::TexturePlugin;
-use ui::UiPlugin;
+use dprmapedit::autosave::AutosavePlugin;
+use dprmapedit::camera::CameraPlugin;
+use dprmapedit::controls::ControlsPlugin;
+use dprmapedit::debug::asset::image_inspector::ImageInspectorPlugin;
+use dprmapedit::editor::EditorPlugin;
+use dprmapedit::runtime::RuntimePlugin;
+use dprmapedit::texture::TexturePlugin;
+use dprmapedit::ui::UiPlugin;
+use dprmapedit::walkability_visual::WalkabilityVisualPlugin;
+use dprmapedit::{grid_visual, terrain};
 
 fn main() {
+    if let Some(code) = cli::run(std::env::args().skip(1).collect()) {
+        std::process::exit(code);
+    }
+
     App::new()
-        .add_plugins((DefaultPlugins, EguiPlugin))
+        .add_plugins((
+            DefaultPlugins.set(WindowPlugin {
+                primary_window: Some(Window {
+                    title: "Tilemap Edit 3D".to_string(),
+                    ..default()
+                }),
+                // Closing is routed through the editor so unsaved changes can be confirmed.
+                close_when_requested: false,
+                ..default()
+            }),
+            EguiPlugin,
+        ))
         .configure_sets(
             Update,
             terrain::TerrainMeshSet::Rebuild.before(terrain::TerrainMeshSet::Cleanup),
@@ -36,11 +42,13 @@ fn main() {
             EditorPlugin,
             RuntimePlugin,
             UiPlugin,
+            WalkabilityVisualPlugin,
+            AutosavePlugin,
             ImageInspectorPlugin,
         ))
         .add_systems(Startup, setup_light)
         .add_systems(Update, grid_visual::draw_grid)
-        // .add_systems(Update, material::fix_roughness_images_on_load)
+        // .add_systems(Update, dprmapedit::texture::material::fix_roughness_images_on_load)
         .run();
 }
 
@@ -0,0 +1,307 @@
+//! Loads `.tmemapdata` packages as Bevy assets so games can render exported maps.
+//!
+//! Add [`TileMapPackagePlugin`], then spawn a [`TileMapPackageBundle`] with a handle from
+//! `asset_server.load("maps/level1.tmemapdata")`. Once the package has loaded, one terrain
+//! mesh per chunk is spawned as a child of that entity and the package metadata is inserted on it as
+//! [`TileMapPackageMetadata`]. The terrain shader is still read from
+//! `shaders/terrain_pbr_extension.wgsl`, so games need a copy in their asset folder.
+
+use std::io::Cursor;
+use std::path::Path;
+
+use anyhow::{Context, Result, bail, ensure};
+use bevy::asset::io::Reader;
+use bevy::asset::{AssetLoader, AsyncReadExt, LoadContext};
+use bevy::pbr::MaterialPlugin;
+use bevy::prelude::*;
+use bevy::render::render_asset::RenderAssetUsages;
+use bevy::render::texture::{CompressedImageFormats, ImageSampler, ImageType};
+use zip::ZipArchive;
+
+use crate::export::{ExportMetadata, read_zip_entry};
+use crate::io::decode_map;
+use crate::terrain::{self, splatmap};
+use crate::texture::material::{self, TerrainMaterial};
+use crate::texture::registry::{TerrainTextureArrays, create_fallback_image};
+use crate::types::{TileMap, TileType};
+
+pub struct TileMapPackagePlugin;
+
+impl Plugin for TileMapPackagePlugin {
+    fn build(&self, app: &mut App) {
+        if !app.is_plugin_added::<MaterialPlugin<TerrainMaterial>>() {
+            app.add_plugins(MaterialPlugin::<TerrainMaterial>::default());
+        }
+        app.init_asset::<TileMapPackage>()
+            .register_asset_loader(TileMapPackageLoader)
+            .add_systems(
+                Update,
+                (reset_modified_packages, spawn_package_terrain).chain(),
+            );
+    }
+}
+
+/// A loaded map package. Its chunk meshes, textures and material are labeled sub-assets.
+#[derive(Asset, TypePath)]
+pub struct TileMapPackage {
+    pub metadata: ExportMetadata,
+    pub map: TileMap,
+    /// One mesh per [`terrain::CHUNK_SIZE`] chunk, with the chunk's grid coordinates.
+    pub chunks: Vec<(UVec2, Handle<Mesh>)>,
+    pub material: Handle<TerrainMaterial>,
+    pub splat_map: Handle<Image>,
+}
+
+/// Metadata of the package an entity was spawned from.
+#[derive(Component, Clone, Debug)]
+pub struct TileMapPackageMetadata(pub ExportMetadata);
+
+/// Marks the terrain chunk entities spawned for a package.
+#[derive(Component)]
+pub struct TileMapPackageTerrain;
+
+#[derive(Bundle, Default)]
+pub struct TileMapPackageBundle {
+    pub package: Handle<TileMapPackage>,
+    pub spatial: SpatialBundle,
+}
+
+#[derive(Default)]
+pub struct TileMapPackageLoader;
+
+impl AssetLoader for TileMapPackageLoader {
+    type Asset = TileMapPackage;
+    type Settings = ();
+    type Error = anyhow::Error;
+
+    async fn load<'a>(
+        &'a self,
+        reader: &'a mut Reader<'_>,
+        _settings: &'a (),
+        load_context: &'a mut LoadContext<'_>,
+    ) -> Result<TileMapPackage> {
+        let mut bytes = Vec::new();
+        reader.read_to_end(&mut bytes).await?;
+        load_package(&bytes, load_context)
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["tmemapdata"]
+    }
+}
+
+fn load_package(bytes: &[u8], load_context: &mut LoadContext) -> Result<TileMapPackage> {
+    let mut zip = ZipArchive::new(Cursor::new(bytes)).context("Not a map package")?;
+    let metadata: ExportMetadata =
+        serde_json::from_slice(&read_zip_entry(&mut zip, "metadata.json")?)
+            .context("Invalid package metadata")?;
+    let tilemap_path = metadata.tilemap.as_deref().unwrap_or("tilemap.json");
+    let map = decode_map(&read_zip_entry(&mut zip, tilemap_path)?)
+        .with_context(|| format!("Invalid tile map {tilemap_path} in package"))?;
+
+    let splat = image::load_from_memory(&read_zip_entry(&mut zip, &metadata.splatmap)?)
+        .context("Invalid splat map in package")?
+        .into_rgba8();
+    ensure!(
+        splat.dimensions() == (map.width.max(1), map.height.max(1)),
+        "Package splat map does not match the map size"
+    );
+    let splat_image = splatmap::from_rgba8(splat.width(), splat.height(), splat.into_raw());
+
+    let mut read_image = |path: &str| -> Result<Image> {
+        let bytes = read_zip_entry(&mut zip, path)?;
+        let extension = Path::new(path)
+            .extension()
+            .and_then(|ext| ext.to_str())
+            .unwrap_or("png");
+        // Match the asset server's default image settings, which the editor loads with.
+        Image::from_buffer(
+            &bytes,
+            ImageType::Extension(extension),
+            CompressedImageFormats::NONE,
+            true,
+            ImageSampler::Default,
+            RenderAssetUsages::default(),
+        )
+        .with_context(|| format!("Invalid texture {path} in package"))
+    };
+
+    // Layers follow `TileType::ALL` like the editor registry, then the optional wall.
+    let mut diffuse = Vec::new();
+    let mut normal = Vec::new();
+    let mut roughness = Vec::new();
+    for tile_type in TileType::ALL {
+        let entry = metadata
+            .textures
+            .iter()
+            .find(|entry| entry.tile_type() == Some(tile_type));
+        match entry {
+            Some(entry) => {
+                diffuse.push(Some(read_image(&entry.diffuse)?));
+                normal.push(entry.normal.as_deref().map(&mut read_image).transpose()?);
+                roughness.push(
+                    entry
+                        .roughness
+                        .as_deref()
+                        .map(&mut read_image)
+                        .transpose()?,
+                );
+            }
+            None => {
+                diffuse.push(None);
+                normal.push(None);
+                roughness.push(None);
+            }
+        }
+    }
+    let floor_layers = diffuse.len() as u32;
+    let wall_layer_index = metadata.wall_texture.as_ref().map(|_| floor_layers);
+    if let Some(wall) = metadata.wall_texture.as_ref() {
+        diffuse.push(Some(read_image(&wall.diffuse)?));
+        normal.push(wall.normal.as_deref().map(&mut read_image).transpose()?);
+        roughness.push(wall.roughness.as_deref().map(&mut read_image).transpose()?);
+    }
+    let wall_has_normal = wall_layer_index.is_some() && normal.last().is_some_and(Option::is_some);
+    let wall_has_roughness =
+        wall_layer_index.is_some() && roughness.last().is_some_and(Option::is_some);
+
+    // Tile types the package has no texture for are never painted, so any image will do.
+    let Some(template) = diffuse.iter().flatten().next().cloned() else {
+        bail!("Package has no terrain textures");
+    };
+    let base_layers: Vec<Image> = diffuse
+        .into_iter()
+        .map(|image| image.unwrap_or_else(|| template.clone()))
+        .collect();
+    let base_color = build_array(&base_layers.iter().map(Some).collect::<Vec<_>>(), [0.0; 4])
+        .context("Package diffuse textures differ in size or format")?;
+    let normal = build_optional_array(&normal, [0.5, 0.5, 1.0, 1.0])
+        .context("Package normal maps differ in size or format")?;
+    let roughness = build_optional_array(&roughness, [1.0, 1.0, 1.0, 1.0])
+        .context("Package roughness maps differ in size or format")?;
+
+    let arrays = TerrainTextureArrays {
+        base_color: load_context.add_labeled_asset("BaseColorArray".to_string(), base_color),
+        normal: normal
+            .map(|image| load_context.add_labeled_asset("NormalArray".to_string(), image)),
+        roughness: roughness
+            .map(|image| load_context.add_labeled_asset("RoughnessArray".to_string(), image)),
+        wall_layer_index,
+        wall_has_normal,
+        wall_has_roughness,
+    };
+    let splat_map = load_context.add_labeled_asset("SplatMap".to_string(), splat_image);
+
+    let mut terrain_material = material::runtime_material();
+    material::apply_texture_arrays(
+        &mut terrain_material.extension,
+        &arrays,
+        floor_layers,
+        &splat_map,
+        UVec2::new(map.width.max(1), map.height.max(1)),
+    );
+    let material = load_context.add_labeled_asset("Material".to_string(), terrain_material);
+    let chunks = terrain::build_chunk_meshes(&map, metadata.top_normals)
+        .into_iter()
+        .map(|(coords, mesh)| {
+            let label = format!("Chunk{}_{}", coords.x, coords.y);
+            (coords, load_context.add_labeled_asset(label, mesh))
+        })
+        .collect();
+
+    Ok(TileMapPackage {
+        metadata,
+        map,
+        chunks,
+        material,
+        splat_map,
+    })
+}
+
+/// Builds a texture array from optional layers, or `None` if no layer has an image.
+fn build_optional_array(layers: &[Option<Image>], fallback: [f32; 4]) -> Result<Option<Image>> {
+    if layers.iter().all(Option::is_none) {
+        return Ok(None);
+    }
+    let refs: Vec<Option<&Image>> = layers.iter().map(Option::as_ref).collect();
+    build_array(&refs, fallback).map(Some)
+}
+
+/// Stacks the layers with `create_texture_array_image`, filling gaps with `fallback`.
+fn build_array(layers: &[Option<&Image>], fallback: [f32; 4]) -> Result<Image> {
+    let template = layers
+        .iter()
+        .flatten()
+        .next()
+        .context("Texture array has no layers")?;
+    let fallback = create_fallback_image(template, fallback);
+    let mut stacked = Vec::with_capacity(layers.len());
+    for layer in layers {
+        match (layer, fallback.as_ref()) {
+            (Some(image), _) => stacked.push(*image),
+            (None, Some(fallback)) => stacked.push(fallback),
+            (None, None) => bail!(
+                "Unsupported texture format {:?}",
+                template.texture_descriptor.format
+            ),
+        }
+    }
+    material::create_texture_array_image(&stacked).context("Texture layers do not match")
+}
+
+type UnspawnedPackages<'w, 's> =
+    Query<'w, 's, (Entity, &'static Handle<TileMapPackage>), Without<TileMapPackageMetadata>>;
+
+fn spawn_package_terrain(
+    mut commands: Commands,
+    packages: Res<Assets<TileMapPackage>>,
+    query: UnspawnedPackages,
+) {
+    for (entity, handle) in &query {
+        let Some(package) = packages.get(handle) else {
+            continue;
+        };
+        commands
+            .entity(entity)
+            .insert(TileMapPackageMetadata(package.metadata.clone()))
+            .with_children(|parent| {
+                for (coords, mesh) in &package.chunks {
+                    parent.spawn((
+                        MaterialMeshBundle {
+                            mesh: mesh.clone(),
+                            material: package.material.clone(),
+                            ..default()
+                        },
+                        TileMapPackageTerrain,
+                        Name::new(format!(
+                            "TileMapTerrain::{}({}, {})",
+                            package.metadata.name, coords.x, coords.y
+                        )),
+                    ));
+                }
+            });
+    }
+}
+
+/// Despawns the terrain of packages that were reloaded so it is spawned again.
+fn reset_modified_packages(
+    mut commands: Commands,
+    mut events: EventReader<AssetEvent<TileMapPackage>>,
+    spawned: Query<(Entity, &Handle<TileMapPackage>, &Children), With<TileMapPackageMetadata>>,
+    terrain: Query<(), With<TileMapPackageTerrain>>,
+) {
+    for event in events.read() {
+        let AssetEvent::Modified { id } = event else {
+            continue;
+        };
+        for (entity, handle, children) in &spawned {
+            if handle.id() != *id {
+                continue;
+            }
+            for child in children.iter().filter(|child| terrain.contains(**child)) {
+                commands.entity(*child).despawn_recursive();
+            }
+            commands.entity(entity).remove::<TileMapPackageMetadata>();
+        }
+    }
+}
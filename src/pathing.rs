@@ -0,0 +1,499 @@
+use std::cmp::Ordering;
+use std::collections::BinaryHeap;
+use std::f32::consts::SQRT_2;
+
+use bevy::math::Vec3;
+
+use crate::terrain::{self, CORNER_NE, CORNER_NW, CORNER_SE, CORNER_SW};
+use crate::types::{TILE_SIZE, TileMap, WalkOverride};
+
+const WALK_GRID_MAGIC: &[u8; 4] = b"TMWG";
+const WALK_GRID_VERSION: u16 = 1;
+const HEIGHT_EPSILON: f32 = 1e-4;
+
+/// One of the eight neighbours of a grid cell. The discriminant is the bit
+/// index used in [`WalkGrid`] connection masks.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Neighbor {
+    North,
+    NorthEast,
+    East,
+    SouthEast,
+    South,
+    SouthWest,
+    West,
+    NorthWest,
+}
+
+impl Neighbor {
+    pub const ALL: [Neighbor; 8] = [
+        Neighbor::North,
+        Neighbor::NorthEast,
+        Neighbor::East,
+        Neighbor::SouthEast,
+        Neighbor::South,
+        Neighbor::SouthWest,
+        Neighbor::West,
+        Neighbor::NorthWest,
+    ];
+
+    pub fn offset(self) -> (i32, i32) {
+        match self {
+            Neighbor::North => (0, -1),
+            Neighbor::NorthEast => (1, -1),
+            Neighbor::East => (1, 0),
+            Neighbor::SouthEast => (1, 1),
+            Neighbor::South => (0, 1),
+            Neighbor::SouthWest => (-1, 1),
+            Neighbor::West => (-1, 0),
+            Neighbor::NorthWest => (-1, -1),
+        }
+    }
+
+    pub fn bit(self) -> u8 {
+        1 << (self as u8)
+    }
+
+    pub fn is_diagonal(self) -> bool {
+        let (dx, dy) = self.offset();
+        dx != 0 && dy != 0
+    }
+}
+
+/// 8-connected walkability graph derived from the terrain. Each cell stores a
+/// bit mask of the neighbours it can move to directly.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct WalkGrid {
+    pub width: u32,
+    pub height: u32,
+    walkable: Vec<bool>,
+    connections: Vec<u8>,
+}
+
+impl WalkGrid {
+    pub fn idx(&self, x: u32, y: u32) -> usize {
+        (y * self.width + x) as usize
+    }
+
+    pub fn is_walkable(&self, x: u32, y: u32) -> bool {
+        self.walkable[self.idx(x, y)]
+    }
+
+    pub fn connections(&self, x: u32, y: u32) -> u8 {
+        self.connections[self.idx(x, y)]
+    }
+
+    /// Iterates the cells directly reachable from `(x, y)`.
+    pub fn neighbors(&self, x: u32, y: u32) -> impl Iterator<Item = (Neighbor, u32, u32)> + '_ {
+        let mask = self.connections(x, y);
+        Neighbor::ALL
+            .into_iter()
+            .filter(move |neighbor| mask & neighbor.bit() != 0)
+            .map(move |neighbor| {
+                let (dx, dy) = neighbor.offset();
+                (neighbor, (x as i32 + dx) as u32, (y as i32 + dy) as u32)
+            })
+    }
+
+    /// Encodes the grid in the compact binary layout shipped in map packages:
+    ///
+    /// | bytes         | content                                   |
+    /// |---------------|-------------------------------------------|
+    /// | 4             | magic `TMWG`                              |
+    /// | 2             | format version (little endian)            |
+    /// | 2             | reserved, zero                            |
+    /// | 4 + 4         | width, height (little endian)             |
+    /// | width*height  | neighbour mask per cell, row-major        |
+    /// | ceil(w*h / 8) | walkable bit set, row-major, LSB first    |
+    ///
+    /// Mask bit `n` is set when the cell connects to neighbour `n` in the order
+    /// N, NE, E, SE, S, SW, W, NW.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let cell_count = self.connections.len();
+        let mut bytes = Vec::with_capacity(16 + cell_count + cell_count.div_ceil(8));
+        bytes.extend_from_slice(WALK_GRID_MAGIC);
+        bytes.extend_from_slice(&WALK_GRID_VERSION.to_le_bytes());
+        bytes.extend_from_slice(&0u16.to_le_bytes());
+        bytes.extend_from_slice(&self.width.to_le_bytes());
+        bytes.extend_from_slice(&self.height.to_le_bytes());
+        bytes.extend_from_slice(&self.connections);
+
+        let mut bits = vec![0u8; cell_count.div_ceil(8)];
+        for (index, walkable) in self.walkable.iter().enumerate() {
+            if *walkable {
+                bits[index / 8] |= 1 << (index % 8);
+            }
+        }
+        bytes.extend_from_slice(&bits);
+        bytes
+    }
+}
+
+/// Derives the walkability graph from the terrain geometry. Two cardinal
+/// neighbours connect when their shared edge has matching corner heights, which
+/// is exactly when `append_tile_geometry` emits no side face between them. That
+/// lets ramps join levels while cliffs (including forced ramp cliffs) block.
+/// Diagonal moves require both adjacent cardinal moves so paths never cut
+/// cliff corners.
+pub fn build_walk_grid(map: &TileMap) -> WalkGrid {
+    let cell_count = (map.width * map.height) as usize;
+    let mut grid = WalkGrid {
+        width: map.width,
+        height: map.height,
+        walkable: vec![true; cell_count],
+        connections: vec![0; cell_count],
+    };
+
+    if cell_count == 0 {
+        return grid;
+    }
+
+    let mut overrides = vec![None; cell_count];
+    for cell in &map.walk_overrides {
+        if cell.x < map.width && cell.y < map.height {
+            let index = map.idx(cell.x, cell.y);
+            overrides[index] = Some(cell.mode);
+            grid.walkable[index] = cell.mode != WalkOverride::Blocked;
+        }
+    }
+
+    let mut corner_cache = vec![[0.0f32; 4]; cell_count];
+    for y in 0..map.height {
+        for x in 0..map.width {
+            corner_cache[map.idx(x, y)] = terrain::tile_corner_heights(map, x, y);
+        }
+    }
+
+    let cardinal_open = |x: u32, y: u32, neighbor: Neighbor| -> bool {
+        let (dx, dy) = neighbor.offset();
+        let nx = x as i32 + dx;
+        let ny = y as i32 + dy;
+        if nx < 0 || ny < 0 || nx as u32 >= map.width || ny as u32 >= map.height {
+            return false;
+        }
+        let (nx, ny) = (nx as u32, ny as u32);
+        let a = map.idx(x, y);
+        let b = map.idx(nx, ny);
+        if !grid.walkable[a] || !grid.walkable[b] {
+            return false;
+        }
+        if overrides[a] == Some(WalkOverride::Open) || overrides[b] == Some(WalkOverride::Open) {
+            return true;
+        }
+
+        let (ca, cb) = (corner_cache[a], corner_cache[b]);
+        let (edge_a, edge_b) = match neighbor {
+            Neighbor::North => (
+                [ca[CORNER_NW], ca[CORNER_NE]],
+                [cb[CORNER_SW], cb[CORNER_SE]],
+            ),
+            Neighbor::South => (
+                [ca[CORNER_SW], ca[CORNER_SE]],
+                [cb[CORNER_NW], cb[CORNER_NE]],
+            ),
+            Neighbor::East => (
+                [ca[CORNER_NE], ca[CORNER_SE]],
+                [cb[CORNER_NW], cb[CORNER_SW]],
+            ),
+            Neighbor::West => (
+                [ca[CORNER_NW], ca[CORNER_SW]],
+                [cb[CORNER_NE], cb[CORNER_SE]],
+            ),
+            _ => return false,
+        };
+        (edge_a[0] - edge_b[0]).abs() < HEIGHT_EPSILON
+            && (edge_a[1] - edge_b[1]).abs() < HEIGHT_EPSILON
+    };
+
+    let mut cardinal = vec![0u8; cell_count];
+    for y in 0..map.height {
+        for x in 0..map.width {
+            let mut mask = 0u8;
+            for neighbor in [
+                Neighbor::North,
+                Neighbor::East,
+                Neighbor::South,
+                Neighbor::West,
+            ] {
+                if cardinal_open(x, y, neighbor) {
+                    mask |= neighbor.bit();
+                }
+            }
+            cardinal[map.idx(x, y)] = mask;
+        }
+    }
+
+    for y in 0..map.height {
+        for x in 0..map.width {
+            let index = map.idx(x, y);
+            let mut mask = cardinal[index];
+            for neighbor in Neighbor::ALL.into_iter().filter(|n| n.is_diagonal()) {
+                let (dx, dy) = neighbor.offset();
+                let horizontal = if dx > 0 {
+                    Neighbor::East
+                } else {
+                    Neighbor::West
+                };
+                let vertical = if dy > 0 {
+                    Neighbor::South
+                } else {
+                    Neighbor::North
+                };
+                if cardinal[index] & horizontal.bit() == 0 || cardinal[index] & vertical.bit() == 0
+                {
+                    continue;
+                }
+                let side_x = map.idx((x as i32 + dx) as u32, y);
+                let side_y = map.idx(x, (y as i32 + dy) as u32);
+                if cardinal[side_x] & vertical.bit() != 0
+                    && cardinal[side_y] & horizontal.bit() != 0
+                {
+                    mask |= neighbor.bit();
+                }
+            }
+            grid.connections[index] = mask;
+        }
+    }
+
+    grid
+}
+
+/// Result of an A* search over a [`WalkGrid`].
+#[derive(Clone, Debug)]
+pub struct PathResult {
+    pub cells: Vec<(u32, u32)>,
+    /// Path cost in tiles, counting diagonal steps as `sqrt(2)`.
+    pub length_tiles: f32,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+struct OpenNode {
+    estimate: f32,
+    index: usize,
+}
+
+impl Eq for OpenNode {}
+
+impl Ord for OpenNode {
+    fn cmp(&self, other: &Self) -> Ordering {
+        // Reversed so the `BinaryHeap` pops the lowest estimate first.
+        other
+            .estimate
+            .total_cmp(&self.estimate)
+            .then_with(|| other.index.cmp(&self.index))
+    }
+}
+
+impl PartialOrd for OpenNode {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+/// A* search with an octile heuristic. Returns `None` when either end is out of
+/// bounds or unwalkable, or when no route exists.
+pub fn find_path(grid: &WalkGrid, start: (u32, u32), goal: (u32, u32)) -> Option<PathResult> {
+    let in_bounds = |(x, y): (u32, u32)| x < grid.width && y < grid.height;
+    if !in_bounds(start) || !in_bounds(goal) {
+        return None;
+    }
+    if !grid.is_walkable(start.0, start.1) || !grid.is_walkable(goal.0, goal.1) {
+        return None;
+    }
+
+    let heuristic = |x: u32, y: u32| -> f32 {
+        let dx = (x as f32 - goal.0 as f32).abs();
+        let dy = (y as f32 - goal.1 as f32).abs();
+        dx.max(dy) + (SQRT_2 - 1.0) * dx.min(dy)
+    };
+
+    let cell_count = (grid.width * grid.height) as usize;
+    let mut cost = vec![f32::INFINITY; cell_count];
+    let mut came_from = vec![usize::MAX; cell_count];
+    let mut closed = vec![false; cell_count];
+    let mut open = BinaryHeap::new();
+
+    let start_index = grid.idx(start.0, start.1);
+    let goal_index = grid.idx(goal.0, goal.1);
+    cost[start_index] = 0.0;
+    open.push(OpenNode {
+        estimate: heuristic(start.0, start.1),
+        index: start_index,
+    });
+
+    while let Some(OpenNode { index, .. }) = open.pop() {
+        if index == goal_index {
+            break;
+        }
+        if closed[index] {
+            continue;
+        }
+        closed[index] = true;
+
+        let x = index as u32 % grid.width;
+        let y = index as u32 / grid.width;
+        for (neighbor, nx, ny) in grid.neighbors(x, y) {
+            let next = grid.idx(nx, ny);
+            if closed[next] {
+                continue;
+            }
+            let step = if neighbor.is_diagonal() { SQRT_2 } else { 1.0 };
+            let candidate = cost[index] + step;
+            if candidate < cost[next] {
+                cost[next] = candidate;
+                came_from[next] = index;
+                open.push(OpenNode {
+                    estimate: candidate + heuristic(nx, ny),
+                    index: next,
+                });
+            }
+        }
+    }
+
+    if !cost[goal_index].is_finite() {
+        return None;
+    }
+
+    let mut cells = Vec::new();
+    let mut current = goal_index;
+    loop {
+        cells.push((current as u32 % grid.width, current as u32 / grid.width));
+        if current == start_index {
+            break;
+        }
+        current = came_from[current];
+    }
+    cells.reverse();
+
+    Some(PathResult {
+        cells,
+        length_tiles: cost[goal_index],
+    })
+}
+
+/// Builds a world-space polyline for a path that hugs the terrain surface. Each
+/// step passes through the shared edge midpoint (or shared corner for diagonal
+/// steps) so ramps are followed instead of cut through.
+pub fn path_polyline(map: &TileMap, cells: &[(u32, u32)]) -> Vec<Vec3> {
+    let center = |x: u32, y: u32| -> Vec3 {
+        let heights = terrain::tile_corner_heights(map, x, y);
+        Vec3::new(
+            (x as f32 + 0.5) * TILE_SIZE,
+            heights.iter().sum::<f32>() / 4.0,
+            (y as f32 + 0.5) * TILE_SIZE,
+        )
+    };
+
+    let mut points = Vec::with_capacity(cells.len() * 2);
+    for (step, &(x, y)) in cells.iter().enumerate() {
+        if step > 0 {
+            let (px, py) = cells[step - 1];
+            let corners = terrain::tile_corner_heights(map, px, py);
+            let dx = x as i32 - px as i32;
+            let dy = y as i32 - py as i32;
+            let edge_height = match (dx, dy) {
+                (0, -1) => (corners[CORNER_NW] + corners[CORNER_NE]) * 0.5,
+                (0, 1) => (corners[CORNER_SW] + corners[CORNER_SE]) * 0.5,
+                (1, 0) => (corners[CORNER_NE] + corners[CORNER_SE]) * 0.5,
+                (-1, 0) => (corners[CORNER_NW] + corners[CORNER_SW]) * 0.5,
+                (1, -1) => corners[CORNER_NE],
+                (1, 1) => corners[CORNER_SE],
+                (-1, 1) => corners[CORNER_SW],
+                _ => corners[CORNER_NW],
+            };
+            points.push(Vec3::new(
+                (px as f32 + 0.5 + dx as f32 * 0.5) * TILE_SIZE,
+                edge_height,
+                (py as f32 + 0.5 + dy as f32 * 0.5) * TILE_SIZE,
+            ));
+        }
+        points.push(center(x, y));
+    }
+    points
+}
+
+pub fn polyline_length(points: &[Vec3]) -> f32 {
+    points
+        .windows(2)
+        .map(|pair| pair[0].distance(pair[1]))
+        .sum()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::{RampDirection, TILE_HEIGHT, TileKind};
+
+    fn set_elevation(map: &mut TileMap, x: u32, y: u32, elevation: i8) {
+        let index = map.idx(x, y);
+        map.tiles[index].elevation = elevation;
+    }
+
+    #[test]
+    fn flat_map_connects_every_neighbour() {
+        let grid = build_walk_grid(&TileMap::new(3, 3));
+        assert_eq!(grid.connections(1, 1), 0xff);
+        let corner = Neighbor::East.bit() | Neighbor::SouthEast.bit() | Neighbor::South.bit();
+        assert_eq!(grid.connections(0, 0), corner);
+    }
+
+    #[test]
+    fn diagonal_moves_do_not_cut_cliff_corners() {
+        let mut map = TileMap::new(3, 3);
+        set_elevation(&mut map, 1, 0, 1);
+        let grid = build_walk_grid(&map);
+
+        assert_eq!(grid.connections(0, 0), Neighbor::South.bit());
+        assert_eq!(grid.connections(1, 1) & Neighbor::NorthWest.bit(), 0);
+        assert_eq!(grid.connections(1, 1) & Neighbor::NorthEast.bit(), 0);
+
+        // Around the raised tile: down, two steps east and back up.
+        let path = find_path(&grid, (0, 0), (2, 0)).unwrap();
+        assert_eq!(path.cells, vec![(0, 0), (0, 1), (1, 1), (2, 1), (2, 0)]);
+        assert_eq!(path.length_tiles, 4.0);
+    }
+
+    #[test]
+    fn overrides_open_cliffs_and_block_cells() {
+        let mut map = TileMap::new(3, 1);
+        set_elevation(&mut map, 1, 0, 2);
+        assert!(find_path(&build_walk_grid(&map), (0, 0), (2, 0)).is_none());
+
+        map.set_walk_override(1, 0, Some(WalkOverride::Open));
+        let path = find_path(&build_walk_grid(&map), (0, 0), (2, 0)).unwrap();
+        assert_eq!(path.cells, vec![(0, 0), (1, 0), (2, 0)]);
+
+        let mut map = TileMap::new(3, 1);
+        map.set_walk_override(1, 0, Some(WalkOverride::Blocked));
+        let grid = build_walk_grid(&map);
+        assert!(!grid.is_walkable(1, 0));
+        assert_eq!(grid.connections(0, 0), 0);
+        assert!(find_path(&grid, (0, 0), (2, 0)).is_none());
+        assert!(find_path(&grid, (0, 0), (1, 0)).is_none());
+    }
+
+    #[test]
+    fn ramps_connect_levels_and_the_polyline_follows_them() {
+        let mut map = TileMap::new(3, 1);
+        set_elevation(&mut map, 1, 0, 1);
+        set_elevation(&mut map, 2, 0, 1);
+        assert!(find_path(&build_walk_grid(&map), (0, 0), (2, 0)).is_none());
+
+        let index = map.idx(1, 0);
+        map.tiles[index].kind = TileKind::Ramp;
+        map.tiles[index].ramp_direction = Some(RampDirection::West);
+        let path = find_path(&build_walk_grid(&map), (0, 0), (2, 0)).unwrap();
+        assert_eq!(path.cells, vec![(0, 0), (1, 0), (2, 0)]);
+        assert_eq!(path.length_tiles, 2.0);
+
+        let heights: Vec<f32> = path_polyline(&map, &path.cells)
+            .iter()
+            .map(|point| point.y)
+            .collect();
+        let ramp_center = TILE_HEIGHT * 0.5;
+        assert_eq!(
+            heights,
+            vec![0.0, 0.0, ramp_center, TILE_HEIGHT, TILE_HEIGHT]
+        );
+    }
+}
@@ -0,0 +1,479 @@
+//! Height, normal and raycast queries against the terrain surface that
+//! `terrain::build_combined_mesh` renders.
+
+use bevy::math::{Ray3d, Vec2, Vec3};
+
+use crate::terrain::{self, CORNER_NE, CORNER_NW, CORNER_SE, CORNER_SW};
+use crate::types::{RampDirection, TILE_SIZE, TileMap};
+
+const EPSILON: f32 = 1e-4;
+
+/// Which part of a tile a ray hit.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum TerrainFace {
+    Top,
+    /// The cliff or ramp side wall on the given edge of the tile.
+    Side(RampDirection),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct TerrainHit {
+    pub tile: (u32, u32),
+    pub face: TerrainFace,
+    pub point: Vec3,
+    /// Geometric normal of the face that was hit, pointing out of the terrain.
+    pub normal: Vec3,
+    pub distance: f32,
+}
+
+/// Read-only view of a map for ground queries in world space.
+///
+/// Tiles are planar (flat or a single slope), so bilinear sampling of
+/// `tile_corner_heights` matches the triangulated mesh exactly. Side walls
+/// run from a tile's edge down to the lower neighbour edge, or to zero along
+/// the map border, the same as the generated mesh.
+#[derive(Clone, Copy)]
+pub struct TerrainQuery<'a> {
+    map: &'a TileMap,
+}
+
+impl<'a> TerrainQuery<'a> {
+    pub fn new(map: &'a TileMap) -> Self {
+        Self { map }
+    }
+
+    /// The tile under world position `(x, z)`. Points on the far map border belong to the last tile.
+    pub fn tile_at(&self, x: f32, z: f32) -> Option<(u32, u32)> {
+        let tx = grid_coord(x, self.map.width)?;
+        let ty = grid_coord(z, self.map.height)?;
+        Some((tx, ty))
+    }
+
+    /// Ground height at world position `(x, z)`, or `None` outside the map.
+    pub fn height_at(&self, x: f32, z: f32) -> Option<f32> {
+        let (tx, ty) = self.tile_at(x, z)?;
+        let corners = terrain::tile_corner_heights(self.map, tx, ty);
+        let (u, v) = local_coords(tx, ty, x, z);
+        let north = corners[CORNER_NW] + (corners[CORNER_NE] - corners[CORNER_NW]) * u;
+        let south = corners[CORNER_SW] + (corners[CORNER_SE] - corners[CORNER_SW]) * u;
+        Some(north + (south - north) * v)
+    }
+
+    /// Upward surface normal at world position `(x, z)`, or `None` outside the map.
+    pub fn normal_at(&self, x: f32, z: f32) -> Option<Vec3> {
+        let (tx, ty) = self.tile_at(x, z)?;
+        let corners = terrain::tile_corner_heights(self.map, tx, ty);
+        let (u, v) = local_coords(tx, ty, x, z);
+        let dx = ((corners[CORNER_NE] - corners[CORNER_NW]) * (1.0 - v)
+            + (corners[CORNER_SE] - corners[CORNER_SW]) * v)
+            / TILE_SIZE;
+        let dz = ((corners[CORNER_SW] - corners[CORNER_NW]) * (1.0 - u)
+            + (corners[CORNER_SE] - corners[CORNER_NE]) * u)
+            / TILE_SIZE;
+        Some(Vec3::new(-dx, 1.0, -dz).normalize())
+    }
+
+    /// Top and bottom heights of a tile's side wall at both ends of its edge,
+    /// ordered by increasing x for north/south walls and increasing z for east/west walls.
+    /// The wall is empty where top and bottom are equal.
+    pub fn side_heights(&self, x: u32, y: u32, side: RampDirection) -> ([f32; 2], [f32; 2]) {
+        let corners = terrain::tile_corner_heights(self.map, x, y);
+        let (top, facing) = match side {
+            RampDirection::North => (
+                [corners[CORNER_NW], corners[CORNER_NE]],
+                [CORNER_SW, CORNER_SE],
+            ),
+            RampDirection::South => (
+                [corners[CORNER_SW], corners[CORNER_SE]],
+                [CORNER_NW, CORNER_NE],
+            ),
+            RampDirection::West => (
+                [corners[CORNER_NW], corners[CORNER_SW]],
+                [CORNER_NE, CORNER_SE],
+            ),
+            RampDirection::East => (
+                [corners[CORNER_NE], corners[CORNER_SE]],
+                [CORNER_NW, CORNER_SW],
+            ),
+        };
+        let neighbor = match self.neighbor(x, y, side) {
+            Some((nx, ny)) => {
+                let neighbor = terrain::tile_corner_heights(self.map, nx, ny);
+                [neighbor[facing[0]], neighbor[facing[1]]]
+            }
+            None => [0.0, 0.0],
+        };
+        (top, [top[0].min(neighbor[0]), top[1].min(neighbor[1])])
+    }
+
+    /// First terrain surface hit by `ray` within `max_distance`, walking the grid
+    /// cell by cell so only tiles under the ray are tested.
+    pub fn raycast(&self, ray: Ray3d, max_distance: f32) -> Option<TerrainHit> {
+        if self.map.width == 0 || self.map.height == 0 {
+            return None;
+        }
+        let origin = ray.origin;
+        let dir = *ray.direction;
+        let extent = Vec2::new(
+            self.map.width as f32 * TILE_SIZE,
+            self.map.height as f32 * TILE_SIZE,
+        );
+
+        // Clip the ray to the map footprint and remember the wall it enters through.
+        let mut t_enter = 0.0f32;
+        let mut t_end = max_distance;
+        let mut entry_side = None;
+        for (o, d, size, sides) in [
+            (
+                origin.x,
+                dir.x,
+                extent.x,
+                (RampDirection::West, RampDirection::East),
+            ),
+            (
+                origin.z,
+                dir.z,
+                extent.y,
+                (RampDirection::North, RampDirection::South),
+            ),
+        ] {
+            if d.abs() < EPSILON {
+                if o < 0.0 || o > size {
+                    return None;
+                }
+                continue;
+            }
+            let (near, far) = ((0.0 - o) / d, (size - o) / d);
+            let (near, far, side) = if near <= far {
+                (near, far, sides.0)
+            } else {
+                (far, near, sides.1)
+            };
+            if near > t_enter {
+                t_enter = near;
+                entry_side = Some(side);
+            }
+            t_end = t_end.min(far);
+        }
+        if t_enter > t_end {
+            return None;
+        }
+
+        let start = origin + dir * t_enter;
+        let (mut cx, mut cy) = (
+            clamp_cell(start.x, self.map.width),
+            clamp_cell(start.z, self.map.height),
+        );
+        if let Some(side) = entry_side
+            && let Some(hit) = self.side_hit(ray, cx, cy, side, t_end)
+        {
+            return Some(hit);
+        }
+
+        let step_x = if dir.x > 0.0 { 1 } else { -1 };
+        let step_y = if dir.z > 0.0 { 1 } else { -1 };
+        let mut t_next_x = next_boundary(origin.x, dir.x, cx);
+        let mut t_next_y = next_boundary(origin.z, dir.z, cy);
+        let t_delta_x = TILE_SIZE / dir.x.abs();
+        let t_delta_y = TILE_SIZE / dir.z.abs();
+
+        loop {
+            let t_exit = t_next_x.min(t_next_y).min(t_end);
+            if let Some(hit) = self.top_hit(ray, cx, cy, t_exit) {
+                return Some(hit);
+            }
+            if t_exit >= t_end {
+                return None;
+            }
+
+            let (side, nx, ny) = if t_next_x < t_next_y {
+                t_next_x += t_delta_x;
+                let side = if step_x > 0 {
+                    RampDirection::East
+                } else {
+                    RampDirection::West
+                };
+                (side, cx as i64 + step_x, cy as i64)
+            } else {
+                t_next_y += t_delta_y;
+                let side = if step_y > 0 {
+                    RampDirection::South
+                } else {
+                    RampDirection::North
+                };
+                (side, cx as i64, cy as i64 + step_y)
+            };
+            // Walls on the crossed edge belong to whichever tile is higher there.
+            if let Some(hit) = self.side_hit(ray, cx, cy, side, t_end) {
+                return Some(hit);
+            }
+            if nx < 0 || ny < 0 || nx >= self.map.width as i64 || ny >= self.map.height as i64 {
+                return None;
+            }
+            (cx, cy) = (nx as u32, ny as u32);
+            if let Some(hit) = self.side_hit(ray, cx, cy, side.opposite(), t_end) {
+                return Some(hit);
+            }
+        }
+    }
+
+    fn neighbor(&self, x: u32, y: u32, side: RampDirection) -> Option<(u32, u32)> {
+        let (dx, dy) = side.offset();
+        let nx = x.checked_add_signed(dx)?;
+        let ny = y.checked_add_signed(dy)?;
+        (nx < self.map.width && ny < self.map.height).then_some((nx, ny))
+    }
+
+    /// Intersects the two triangles of the tile top, split along NW-SE like the mesh.
+    fn top_hit(&self, ray: Ray3d, x: u32, y: u32, t_max: f32) -> Option<TerrainHit> {
+        let corners = terrain::tile_corner_heights(self.map, x, y);
+        let x0 = x as f32 * TILE_SIZE;
+        let z0 = y as f32 * TILE_SIZE;
+        let nw = Vec3::new(x0, corners[CORNER_NW], z0);
+        let ne = Vec3::new(x0 + TILE_SIZE, corners[CORNER_NE], z0);
+        let sw = Vec3::new(x0, corners[CORNER_SW], z0 + TILE_SIZE);
+        let se = Vec3::new(x0 + TILE_SIZE, corners[CORNER_SE], z0 + TILE_SIZE);
+
+        [(nw, sw, se), (nw, se, ne)]
+            .into_iter()
+            .filter_map(|(a, b, c)| {
+                let t = ray_triangle(ray, a, b, c)?;
+                (t <= t_max + EPSILON).then(|| {
+                    let normal = (b - a).cross(c - a).normalize_or_zero();
+                    (t, if normal.y < 0.0 { -normal } else { normal })
+                })
+            })
+            .min_by(|a, b| a.0.total_cmp(&b.0))
+            .map(|(t, normal)| TerrainHit {
+                tile: (x, y),
+                face: TerrainFace::Top,
+                point: ray.get_point(t),
+                normal,
+                distance: t,
+            })
+    }
+
+    fn side_hit(
+        &self,
+        ray: Ray3d,
+        x: u32,
+        y: u32,
+        side: RampDirection,
+        t_max: f32,
+    ) -> Option<TerrainHit> {
+        let (top, bottom) = self.side_heights(x, y, side);
+        if top[0] - bottom[0] < EPSILON && top[1] - bottom[1] < EPSILON {
+            return None;
+        }
+
+        let (plane, along_start, normal) = match side {
+            RampDirection::North => (y as f32 * TILE_SIZE, x as f32 * TILE_SIZE, Vec3::NEG_Z),
+            RampDirection::South => ((y + 1) as f32 * TILE_SIZE, x as f32 * TILE_SIZE, Vec3::Z),
+            RampDirection::West => (x as f32 * TILE_SIZE, y as f32 * TILE_SIZE, Vec3::NEG_X),
+            RampDirection::East => ((x + 1) as f32 * TILE_SIZE, y as f32 * TILE_SIZE, Vec3::X),
+        };
+        let across_x = matches!(side, RampDirection::West | RampDirection::East);
+        let (origin, dir) = if across_x {
+            (ray.origin.x, ray.direction.x)
+        } else {
+            (ray.origin.z, ray.direction.z)
+        };
+        if dir.abs() < EPSILON {
+            return None;
+        }
+        let t = (plane - origin) / dir;
+        if t < 0.0 || t > t_max + EPSILON {
+            return None;
+        }
+
+        let point = ray.get_point(t);
+        let along = if across_x { point.z } else { point.x };
+        let s = (along - along_start) / TILE_SIZE;
+        if !(-EPSILON..=1.0 + EPSILON).contains(&s) {
+            return None;
+        }
+        let s = s.clamp(0.0, 1.0);
+        let top = top[0] + (top[1] - top[0]) * s;
+        let bottom = bottom[0] + (bottom[1] - bottom[0]) * s;
+        if point.y < bottom - EPSILON || point.y > top + EPSILON {
+            return None;
+        }
+
+        Some(TerrainHit {
+            tile: (x, y),
+            face: TerrainFace::Side(side),
+            point,
+            normal,
+            distance: t,
+        })
+    }
+}
+
+fn grid_coord(value: f32, cells: u32) -> Option<u32> {
+    if cells == 0 || !value.is_finite() || value < 0.0 {
+        return None;
+    }
+    let cell = (value / TILE_SIZE).floor() as u32;
+    if cell < cells {
+        Some(cell)
+    } else if value <= cells as f32 * TILE_SIZE {
+        Some(cells - 1)
+    } else {
+        None
+    }
+}
+
+fn clamp_cell(value: f32, cells: u32) -> u32 {
+    ((value / TILE_SIZE).floor().max(0.0) as u32).min(cells - 1)
+}
+
+fn local_coords(tx: u32, ty: u32, x: f32, z: f32) -> (f32, f32) {
+    (
+        (x / TILE_SIZE - tx as f32).clamp(0.0, 1.0),
+        (z / TILE_SIZE - ty as f32).clamp(0.0, 1.0),
+    )
+}
+
+/// Ray parameter of the first grid line crossed along one axis.
+fn next_boundary(origin: f32, dir: f32, cell: u32) -> f32 {
+    if dir.abs() < EPSILON {
+        return f32::INFINITY;
+    }
+    let boundary = if dir > 0.0 { cell + 1 } else { cell };
+    (boundary as f32 * TILE_SIZE - origin) / dir
+}
+
+/// Möller–Trumbore intersection, accepting either winding.
+fn ray_triangle(ray: Ray3d, a: Vec3, b: Vec3, c: Vec3) -> Option<f32> {
+    let dir = *ray.direction;
+    let edge1 = b - a;
+    let edge2 = c - a;
+    let p = dir.cross(edge2);
+    let det = edge1.dot(p);
+    if det.abs() < 1e-8 {
+        return None;
+    }
+    let inv_det = 1.0 / det;
+    let s = ray.origin - a;
+    let u = s.dot(p) * inv_det;
+    if !(-EPSILON..=1.0 + EPSILON).contains(&u) {
+        return None;
+    }
+    let q = s.cross(edge1);
+    let v = dir.dot(q) * inv_det;
+    if v < -EPSILON || u + v > 1.0 + EPSILON {
+        return None;
+    }
+    let t = edge2.dot(q) * inv_det;
+    (t >= 0.0).then_some(t)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::{TILE_HEIGHT, TileKind};
+
+    /// A 6x4 map with a raised corner tile on the border, a two-step cliff at
+    /// (3, 1) and a ramp at (1, 2) sloping down to the west.
+    fn sample_map() -> TileMap {
+        let mut map = TileMap::new(6, 4);
+        let index = map.idx(0, 0);
+        map.tiles[index].elevation = 1;
+        let index = map.idx(3, 1);
+        map.tiles[index].elevation = 2;
+        let index = map.idx(1, 2);
+        map.tiles[index].elevation = 1;
+        map.tiles[index].kind = TileKind::Ramp;
+        map.tiles[index].ramp_direction = Some(RampDirection::West);
+        map
+    }
+
+    fn assert_close(a: f32, b: f32) {
+        assert!((a - b).abs() < 1e-4, "{a} != {b}");
+    }
+
+    #[test]
+    fn ramp_height_is_bilinear_in_corner_heights() {
+        let map = sample_map();
+        let query = TerrainQuery::new(&map);
+        let corners = terrain::tile_corner_heights(&map, 1, 2);
+        assert_eq!(corners, [0.0, TILE_HEIGHT, 0.0, TILE_HEIGHT]);
+
+        for (u, v) in [(0.0, 0.0), (0.25, 0.5), (0.5, 0.9), (0.99, 0.99)] {
+            let x = (1.0 + u) * TILE_SIZE;
+            let z = (2.0 + v) * TILE_SIZE;
+            let north = corners[CORNER_NW] + (corners[CORNER_NE] - corners[CORNER_NW]) * u;
+            let south = corners[CORNER_SW] + (corners[CORNER_SE] - corners[CORNER_SW]) * u;
+            assert_close(query.height_at(x, z).unwrap(), north + (south - north) * v);
+        }
+        assert_eq!(query.height_at(-0.1, 1.0), None);
+    }
+
+    #[test]
+    fn downward_ray_hits_top_of_tile_below() {
+        let map = sample_map();
+        let query = TerrainQuery::new(&map);
+
+        let ray = Ray3d::new(Vec3::new(7.0, 10.0, 3.0), Vec3::NEG_Y);
+        let hit = query.raycast(ray, 100.0).unwrap();
+        assert_eq!(hit.tile, (3, 1));
+        assert_eq!(hit.face, TerrainFace::Top);
+        assert_close(hit.point.y, 2.0 * TILE_HEIGHT);
+        assert_close(hit.distance, 10.0 - 2.0 * TILE_HEIGHT);
+        assert!(hit.normal.abs_diff_eq(Vec3::Y, 1e-4));
+
+        let ray = Ray3d::new(Vec3::new(3.0, 10.0, 5.0), Vec3::NEG_Y);
+        let hit = query.raycast(ray, 100.0).unwrap();
+        assert_eq!((hit.tile, hit.face), ((1, 2), TerrainFace::Top));
+        assert_close(hit.point.y, query.height_at(3.0, 5.0).unwrap());
+    }
+
+    #[test]
+    fn horizontal_ray_hits_cliff_side() {
+        let map = sample_map();
+        let query = TerrainQuery::new(&map);
+
+        let ray = Ray3d::new(Vec3::new(1.0, TILE_HEIGHT, 3.0), Vec3::X);
+        let hit = query.raycast(ray, 100.0).unwrap();
+        assert_eq!(hit.tile, (3, 1));
+        assert_eq!(hit.face, TerrainFace::Side(RampDirection::West));
+        assert_eq!(hit.normal, Vec3::NEG_X);
+        assert_close(hit.point.x, 3.0 * TILE_SIZE);
+
+        let ray = Ray3d::new(Vec3::new(11.0, TILE_HEIGHT, 3.0), Vec3::NEG_X);
+        let hit = query.raycast(ray, 100.0).unwrap();
+        assert_eq!(hit.tile, (3, 1));
+        assert_eq!(hit.face, TerrainFace::Side(RampDirection::East));
+        assert_eq!(hit.normal, Vec3::X);
+        assert_close(hit.point.x, 4.0 * TILE_SIZE);
+    }
+
+    #[test]
+    fn ray_from_outside_hits_border_wall() {
+        let map = sample_map();
+        let query = TerrainQuery::new(&map);
+
+        let ray = Ray3d::new(Vec3::new(-5.0, 0.5 * TILE_HEIGHT, 1.0), Vec3::X);
+        let hit = query.raycast(ray, 100.0).unwrap();
+        assert_eq!(hit.tile, (0, 0));
+        assert_eq!(hit.face, TerrainFace::Side(RampDirection::West));
+        assert_eq!(hit.normal, Vec3::NEG_X);
+        assert_close(hit.distance, 5.0);
+
+        // The same ray is too short to reach the map.
+        assert_eq!(query.raycast(ray, 4.0), None);
+    }
+
+    #[test]
+    fn ray_missing_map_returns_none() {
+        let map = sample_map();
+        let query = TerrainQuery::new(&map);
+
+        let beside = Ray3d::new(Vec3::new(20.0, 10.0, 3.0), Vec3::NEG_Y);
+        assert_eq!(query.raycast(beside, 100.0), None);
+        let away = Ray3d::new(Vec3::new(-5.0, 1.0, 3.0), Vec3::NEG_X);
+        assert_eq!(query.raycast(away, 100.0), None);
+        let above = Ray3d::new(Vec3::new(-5.0, 10.0, 3.0), Vec3::X);
+        assert_eq!(query.raycast(above, 100.0), None);
+    }
+}
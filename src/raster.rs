@@ -0,0 +1,239 @@
+use std::io::Cursor;
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result};
+use image::{DynamicImage, GrayImage, ImageBuffer, ImageFormat, Luma};
+use serde::{Deserialize, Serialize};
+
+use crate::terrain::{self, find_ramp_target, ramp_neighbor_height};
+use crate::types::{RampDirection, TILE_HEIGHT, TileKind, TileMap};
+
+/// Encoding used for the 16-bit heightmap.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Serialize, Deserialize)]
+pub enum HeightFormat {
+    /// Single-channel 16-bit PNG.
+    Png16,
+    /// Headerless little-endian `u16` samples, row-major.
+    Raw16,
+}
+
+impl HeightFormat {
+    pub fn extension(self) -> &'static str {
+        match self {
+            HeightFormat::Png16 => "png",
+            HeightFormat::Raw16 => "raw",
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct RasterExportOptions {
+    /// Height samples along each tile edge; more samples resolve ramp slopes better.
+    pub samples_per_tile: u32,
+    pub height_format: HeightFormat,
+}
+
+impl Default for RasterExportOptions {
+    fn default() -> Self {
+        Self {
+            samples_per_tile: 4,
+            height_format: HeightFormat::Png16,
+        }
+    }
+}
+
+/// Describes the raster files so consumers can turn samples back into world units.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct RasterMetadata {
+    pub heightmap: String,
+    pub height_format: HeightFormat,
+    pub height_width: u32,
+    pub height_height: u32,
+    pub samples_per_tile: u32,
+    /// World height of sample value 0.
+    pub height_min: f32,
+    /// World height of sample value 65535.
+    pub height_max: f32,
+    /// 8-bit map with one pixel per tile holding `TileType::as_index`.
+    pub tile_types: String,
+    /// 8-bit map with one pixel per tile: 0 none, 1 north, 2 east, 3 south, 4 west.
+    pub ramps: String,
+}
+
+/// Encoded raster files ready to be written, keyed by file name.
+pub struct RasterFiles {
+    pub metadata: RasterMetadata,
+    pub files: Vec<(String, Vec<u8>)>,
+}
+
+/// Heights sampled across the terrain surface, normalised to the map's height range.
+pub struct HeightRaster {
+    pub width: u32,
+    pub height: u32,
+    pub samples: Vec<u16>,
+    pub min_height: f32,
+    pub max_height: f32,
+}
+
+pub fn height_raster(map: &TileMap, samples_per_tile: u32) -> HeightRaster {
+    let samples_per_tile = samples_per_tile.max(1);
+    let width = map.width * samples_per_tile;
+    let height = map.height * samples_per_tile;
+
+    // Sample each tile's surface at sample centres, interpolating its corner heights
+    // so ramps come out as slopes rather than steps.
+    let mut world = Vec::with_capacity((width * height) as usize);
+    for sy in 0..height {
+        for sx in 0..width {
+            let (tx, ty) = (sx / samples_per_tile, sy / samples_per_tile);
+            let u = ((sx % samples_per_tile) as f32 + 0.5) / samples_per_tile as f32;
+            let v = ((sy % samples_per_tile) as f32 + 0.5) / samples_per_tile as f32;
+            let [nw, ne, sw, se] = terrain::tile_corner_heights(map, tx, ty);
+            let north = nw + (ne - nw) * u;
+            let south = sw + (se - sw) * u;
+            world.push(north + (south - north) * v);
+        }
+    }
+
+    let min_height = world.iter().copied().fold(f32::INFINITY, f32::min);
+    let max_height = world.iter().copied().fold(f32::NEG_INFINITY, f32::max);
+    let (min_height, max_height) = if world.is_empty() {
+        (0.0, 0.0)
+    } else {
+        (min_height, max_height)
+    };
+    // Flat maps still get a usable range of one elevation step.
+    let range = (max_height - min_height).max(TILE_HEIGHT);
+    let max_height = min_height + range;
+
+    let samples = world
+        .iter()
+        .map(|h| (((h - min_height) / range) * u16::MAX as f32).round() as u16)
+        .collect();
+
+    HeightRaster {
+        width,
+        height,
+        samples,
+        min_height,
+        max_height,
+    }
+}
+
+impl HeightRaster {
+    pub fn encode(&self, format: HeightFormat) -> Result<Vec<u8>> {
+        match format {
+            HeightFormat::Png16 => {
+                let image: ImageBuffer<Luma<u16>, Vec<u16>> =
+                    ImageBuffer::from_raw(self.width, self.height, self.samples.clone())
+                        .context("Height raster has the wrong number of samples")?;
+                encode_png(DynamicImage::ImageLuma16(image))
+            }
+            HeightFormat::Raw16 => Ok(self
+                .samples
+                .iter()
+                .flat_map(|sample| sample.to_le_bytes())
+                .collect()),
+        }
+    }
+}
+
+pub fn tile_type_png(map: &TileMap) -> Result<Vec<u8>> {
+    let image = GrayImage::from_fn(map.width, map.height, |x, y| {
+        Luma([map.get(x, y).tile_type.as_index() as u8])
+    });
+    encode_png(DynamicImage::ImageLuma8(image))
+}
+
+pub fn ramp_png(map: &TileMap) -> Result<Vec<u8>> {
+    let image = GrayImage::from_fn(map.width, map.height, |x, y| {
+        Luma([match effective_ramp_direction(map, x, y) {
+            None => 0,
+            Some(RampDirection::North) => 1,
+            Some(RampDirection::East) => 2,
+            Some(RampDirection::South) => 3,
+            Some(RampDirection::West) => 4,
+        }])
+    });
+    encode_png(DynamicImage::ImageLuma8(image))
+}
+
+/// Encodes all rasters, naming each file `{prefix}{kind}.{ext}`.
+pub fn build_rasters(
+    map: &TileMap,
+    options: &RasterExportOptions,
+    prefix: &str,
+) -> Result<RasterFiles> {
+    let heights = height_raster(map, options.samples_per_tile);
+    let metadata = RasterMetadata {
+        heightmap: format!("{prefix}height.{}", options.height_format.extension()),
+        height_format: options.height_format,
+        height_width: heights.width,
+        height_height: heights.height,
+        samples_per_tile: options.samples_per_tile.max(1),
+        height_min: heights.min_height,
+        height_max: heights.max_height,
+        tile_types: format!("{prefix}tile_types.png"),
+        ramps: format!("{prefix}ramps.png"),
+    };
+    let files = vec![
+        (
+            metadata.heightmap.clone(),
+            heights.encode(options.height_format)?,
+        ),
+        (metadata.tile_types.clone(), tile_type_png(map)?),
+        (metadata.ramps.clone(), ramp_png(map)?),
+    ];
+    Ok(RasterFiles { metadata, files })
+}
+
+/// Writes the rasters next to `base_path`, plus a JSON description of them.
+/// Returns the path of the JSON file.
+pub fn export_rasters(
+    base_path: &Path,
+    map: &TileMap,
+    options: &RasterExportOptions,
+) -> Result<PathBuf> {
+    let dir = base_path.parent().unwrap_or(Path::new(""));
+    if !dir.as_os_str().is_empty() {
+        std::fs::create_dir_all(dir)
+            .with_context(|| format!("Failed to create export directory {}", dir.display()))?;
+    }
+    let stem = base_path
+        .file_stem()
+        .and_then(|stem| stem.to_str())
+        .unwrap_or("map");
+
+    let rasters = build_rasters(map, options, &format!("{stem}_"))?;
+    for (name, bytes) in &rasters.files {
+        let path = dir.join(name);
+        std::fs::write(&path, bytes)
+            .with_context(|| format!("Failed to write {}", path.display()))?;
+    }
+
+    let metadata_path = dir.join(format!("{stem}_rasters.json"));
+    std::fs::write(
+        &metadata_path,
+        serde_json::to_vec_pretty(&rasters.metadata)?,
+    )
+    .with_context(|| format!("Failed to write {}", metadata_path.display()))?;
+    Ok(metadata_path)
+}
+
+/// The direction a ramp actually slopes in, resolved the same way as the mesh.
+fn effective_ramp_direction(map: &TileMap, x: u32, y: u32) -> Option<RampDirection> {
+    let tile = map.get(x, y);
+    if tile.kind != TileKind::Ramp {
+        return None;
+    }
+    let base = tile.elevation as f32 * TILE_HEIGHT;
+    tile.ramp_direction
+        .filter(|dir| ramp_neighbor_height(map, x, y, *dir, base).is_some())
+        .or_else(|| find_ramp_target(map, x, y, base).map(|(dir, _)| dir))
+}
+
+fn encode_png(image: DynamicImage) -> Result<Vec<u8>> {
+    let mut bytes = Vec::new();
+    image.write_to(&mut Cursor::new(&mut bytes), ImageFormat::Png)?;
+    Ok(bytes)
+}
@@ -1,13 +1,15 @@
 use crate::editor::EditorState;
-use crate::terrain::{self, TerrainMeshSet, splatmap};
+use crate::terrain::splatmap::{self, SplatPatch};
+use crate::terrain::{self, BuiltChunks, TerrainMeshCache, TerrainMeshSet};
 use crate::texture::material::{self, TerrainMaterial};
 use crate::texture::registry::TerrainTextureRegistry;
-use crate::types::{TILE_SIZE, TileType};
+use crate::types::{TileRect, TileType};
 use bevy::asset::{AssetId, LoadState};
-use bevy::math::{UVec2, Vec2};
+use bevy::math::UVec2;
 use bevy::pbr::MaterialMeshBundle;
 use bevy::prelude::*;
 use bevy::render::texture::Image;
+use bevy::tasks::{AsyncComputeTaskPool, Task, block_on};
 
 pub struct RuntimePlugin;
 
@@ -16,8 +18,8 @@ impl Plugin for RuntimePlugin {
         app.add_systems(Startup, setup_runtime_mesh).add_systems(
             Update,
             (
-                generate_splat_map,
-                rebuild_runtime_mesh,
+                schedule_terrain_build,
+                apply_terrain_build,
                 update_runtime_material,
             )
                 .chain()
@@ -26,11 +28,22 @@ impl Plugin for RuntimePlugin {
     }
 }
 
+/// The live terrain: a root entity with one mesh child per [`terrain::CHUNK_SIZE`] chunk.
+/// All chunks share `material`, and hiding the root hides the whole terrain. Chunks and
+/// the splat map are rebuilt on a background task and keep their previous contents
+/// until it finishes.
 #[derive(Resource)]
 pub struct RuntimeTerrainVisual {
-    pub mesh: Handle<Mesh>,
     pub material: Handle<TerrainMaterial>,
     pub entity: Entity,
+    pub chunks: Vec<RuntimeTerrainChunk>,
+    mesh_cache: TerrainMeshCache,
+    build: Option<TerrainBuild>,
+}
+
+pub struct RuntimeTerrainChunk {
+    pub entity: Entity,
+    pub mesh: Handle<Mesh>,
 }
 
 #[derive(Resource)]
@@ -41,32 +54,23 @@ pub struct RuntimeSplatMap {
 
 fn setup_runtime_mesh(
     mut commands: Commands,
-    mut meshes: ResMut<Assets<Mesh>>,
     mut materials: ResMut<Assets<TerrainMaterial>>,
     mut images: ResMut<Assets<Image>>,
     state: Res<EditorState>,
 ) {
-    let mesh = meshes.add(terrain::empty_mesh());
     let material = material::create_runtime_material(&mut materials);
     let splat_image = splatmap::create(&state.map);
     let splat_handle = images.add(splat_image);
     let entity = commands
-        .spawn((
-            MaterialMeshBundle {
-                mesh: mesh.clone(),
-                material: material.clone(),
-                transform: Transform::default(),
-                visibility: Visibility::Visible,
-                ..default()
-            },
-            Name::new("RuntimeTerrain"),
-        ))
+        .spawn((SpatialBundle::default(), Name::new("RuntimeTerrain")))
         .id();
 
     commands.insert_resource(RuntimeTerrainVisual {
-        mesh,
         material,
         entity,
+        chunks: Vec::new(),
+        mesh_cache: TerrainMeshCache::default(),
+        build: None,
     });
     commands.insert_resource(RuntimeSplatMap {
         handle: splat_handle,
@@ -74,45 +78,129 @@ fn setup_runtime_mesh(
     });
 }
 
-fn rebuild_runtime_mesh(
+/// A terrain rebuild running on the `AsyncComputeTaskPool`, producing the re-meshed
+/// chunks and the updated splat map together so they swap in on the same frame.
+struct TerrainBuild {
+    task: Task<BuiltChunks>,
+    chunks: Vec<usize>,
+    splat_region: Option<TileRect>,
+    /// Applied together with the meshes, so texture and geometry change in the same frame.
+    splat: SplatPatch,
+}
+
+fn schedule_terrain_build(
     state: Res<EditorState>,
-    runtime: Option<Res<RuntimeTerrainVisual>>,
-    mut meshes: ResMut<Assets<Mesh>>,
+    runtime: Option<ResMut<RuntimeTerrainVisual>>,
+    runtime_splat: Option<Res<RuntimeSplatMap>>,
+    images: Res<Assets<Image>>,
 ) {
     if !state.map_dirty {
         return;
     }
 
-    let Some(runtime) = runtime else {
+    let (Some(mut runtime), Some(runtime_splat)) = (runtime, runtime_splat) else {
         return;
     };
 
-    let combined = terrain::build_combined_mesh(&state.map);
+    let mut chunks = runtime.mesh_cache.plan(&state.map, state.dirty_tiles);
+    let mut splat_region = state.dirty_tiles;
+    // Dropping the task cancels it; its chunks and splat pixels are rebuilt from the
+    // newer map instead.
+    if let Some(stale) = runtime.build.take() {
+        chunks.extend(stale.chunks);
+        splat_region = match (splat_region, stale.splat_region) {
+            (Some(rect), Some(stale_rect)) => Some(rect.union(stale_rect)),
+            _ => None,
+        };
+    }
 
-    if let Some(existing) = meshes.get_mut(&runtime.mesh) {
-        *existing = combined;
+    // A missing or differently sized image has to be rewritten in full.
+    let image_fits = images
+        .get(&runtime_splat.handle)
+        .is_some_and(|image| SplatPatch::fits(&state.map, image));
+    if !image_fits {
+        splat_region = None;
     }
+    let splat = SplatPatch::new(
+        &state.map,
+        splat_region.unwrap_or_else(|| TileRect::full(&state.map)),
+    );
+
+    let job = runtime.mesh_cache.build_job(&state.map, chunks);
+    let chunks = job.chunks().collect();
+    let task = AsyncComputeTaskPool::get().spawn(job.run_async());
+    runtime.build = Some(TerrainBuild {
+        task,
+        chunks,
+        splat_region,
+        splat,
+    });
 }
 
-fn generate_splat_map(
-    state: Res<EditorState>,
+fn apply_terrain_build(
+    mut commands: Commands,
+    runtime: Option<ResMut<RuntimeTerrainVisual>>,
     runtime_splat: Option<ResMut<RuntimeSplatMap>>,
+    mut meshes: ResMut<Assets<Mesh>>,
     mut images: ResMut<Assets<Image>>,
 ) {
-    if !state.map_dirty {
-        return;
-    }
-
-    let Some(mut runtime_splat) = runtime_splat else {
+    let Some(mut runtime) = runtime else {
         return;
     };
+    let runtime = &mut *runtime;
 
-    let Some(image) = images.get_mut(&runtime_splat.handle) else {
+    let Some(build) = runtime.build.take_if(|build| build.task.is_finished()) else {
         return;
     };
+    let built = block_on(build.task);
+    let rebuilt = runtime.mesh_cache.apply(built);
+
+    // A different map size changes the chunk grid, so start over with fresh entities.
+    if runtime.chunks.len() != runtime.mesh_cache.chunk_count() {
+        for chunk in runtime.chunks.drain(..) {
+            commands.entity(chunk.entity).despawn_recursive();
+            meshes.remove(&chunk.mesh);
+        }
+        for index in 0..runtime.mesh_cache.chunk_count() {
+            let coords = runtime.mesh_cache.chunk_coords(index);
+            let mesh = meshes.add(terrain::empty_mesh());
+            let entity = commands
+                .spawn((
+                    MaterialMeshBundle {
+                        mesh: mesh.clone(),
+                        material: runtime.material.clone(),
+                        ..default()
+                    },
+                    Name::new(format!("TerrainChunk({}, {})", coords.x, coords.y)),
+                ))
+                .set_parent(runtime.entity)
+                .id();
+            runtime.chunks.push(RuntimeTerrainChunk { entity, mesh });
+        }
+    }
+
+    for index in rebuilt {
+        let chunk = &runtime.chunks[index];
+        let mesh = runtime.mesh_cache.chunk_mesh(index);
+        // Bevy only computes bounds for new entities, so refresh them for culling.
+        if let Some(aabb) = mesh.compute_aabb() {
+            commands.entity(chunk.entity).insert(aabb);
+        }
+        if let Some(existing) = meshes.get_mut(&chunk.mesh) {
+            *existing = mesh;
+        }
+    }
 
-    splatmap::write(&state.map, image);
-    runtime_splat.size = UVec2::new(state.map.width.max(1), state.map.height.max(1));
+    if let Some(mut runtime_splat) = runtime_splat {
+        if images.get(&runtime_splat.handle).is_none() {
+            images.insert(&runtime_splat.handle, Image::default());
+        }
+        if let Some(splat_image) = images.get_mut(&runtime_splat.handle) {
+            build.splat.apply(splat_image);
+            runtime_splat.size =
+                UVec2::new(splat_image.width().max(1), splat_image.height().max(1));
+        }
+    }
 }
 
 fn update_runtime_material(
@@ -236,75 +324,13 @@ fn update_runtime_material(
         .map(|_| desired_layers.saturating_sub(1))
         .unwrap_or(desired_layers);
 
-    if material.extension.params.layer_count != floor_layers {
-        material.extension.params.layer_count = floor_layers;
-    }
-
-    if material
-        .extension
-        .base_color_array
-        .as_ref()
-        .map(|handle| handle != &arrays.base_color)
-        .unwrap_or(true)
-    {
-        material.extension.base_color_array = Some(arrays.base_color.clone());
-    }
-
-    match arrays.normal.clone() {
-        Some(handle) => {
-            if material
-                .extension
-                .normal_array
-                .as_ref()
-                .map(|existing| existing != &handle)
-                .unwrap_or(true)
-            {
-                material.extension.normal_array = Some(handle.clone());
-            }
-        }
-        None => {
-            material.extension.normal_array = None;
-        }
-    }
-
-    match arrays.roughness.clone() {
-        Some(handle) => {
-            if material
-                .extension
-                .roughness_array
-                .as_ref()
-                .map(|existing| existing != &handle)
-                .unwrap_or(true)
-            {
-                material.extension.roughness_array = Some(handle.clone());
-            }
-        }
-        None => {
-            material.extension.roughness_array = None;
-        }
-    }
-
-    if material
-        .extension
-        .splat_map
-        .as_ref()
-        .map(|existing| existing != &splat.handle)
-        .unwrap_or(true)
-    {
-        material.extension.splat_map = Some(splat.handle.clone());
-    }
-
-    material.extension.params.map_size = Vec2::new(splat.size.x as f32, splat.size.y as f32);
-    material.extension.params.tile_size = TILE_SIZE;
-    // The editor mesh already bakes the desired world-space scaling into the
-    // vertex data, so the shader inputs remain at a neutral scale.
-    material.extension.params.height_uv_scale = 1.0;
-    material.extension.params.height_world_scale = 1.0;
-    material.extension.params.cliff_blend_height = 0.2;
-    material.extension.params.wall_enabled = arrays.wall_layer_index.map(|_| 1u32).unwrap_or(0);
-    material.extension.params.wall_layer_index = arrays.wall_layer_index.unwrap_or(u32::MAX);
-    material.extension.params.wall_has_normal = if arrays.wall_has_normal { 1 } else { 0 };
-    material.extension.params.wall_has_roughness = if arrays.wall_has_roughness { 1 } else { 0 };
+    material::apply_texture_arrays(
+        &mut material.extension,
+        &arrays,
+        floor_layers,
+        &splat.handle,
+        splat.size,
+    );
 
     *visibility = Visibility::Visible;
 }
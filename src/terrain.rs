@@ -1,6 +1,7 @@
 use std::collections::HashMap;
 
-use crate::types::{RampDirection, TILE_HEIGHT, TILE_SIZE, TileKind, TileMap, TileType};
+use crate::texture::material::UV_SCALE;
+use crate::types::{RampDirection, TILE_HEIGHT, TILE_SIZE, TileKind, TileMap, TileRect, TileType};
 use bevy::ecs::schedule::SystemSet;
 use bevy::prelude::*;
 use bevy::render::mesh::{Indices, Mesh};
@@ -10,12 +11,29 @@ use bevy::render::render_resource::{
     TextureUsages,
 };
 use bevy::render::texture::{Image, ImageSampler};
+use bevy::tasks::futures_lite;
+use serde::{Deserialize, Serialize};
 
 pub const CORNER_NW: usize = 0;
 pub const CORNER_NE: usize = 1;
 pub const CORNER_SW: usize = 2;
 pub const CORNER_SE: usize = 3;
 
+/// Edge length in tiles of the blocks [`TerrainMeshCache`] re-meshes independently.
+pub const CHUNK_SIZE: u32 = 32;
+
+/// How tile tops are shaded. Cliff walls are always flat, so their edges stay hard.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TopNormals {
+    /// One normal per triangle.
+    #[default]
+    Flat,
+    /// Angle-weighted average of the tops meeting at each corner, so ramps blend
+    /// into the ground around them.
+    Smooth,
+}
+
 #[derive(SystemSet, Debug, Hash, PartialEq, Eq, Clone, Copy)]
 pub enum TerrainMeshSet {
     Rebuild,
@@ -68,30 +86,269 @@ pub fn empty_mesh() -> Mesh {
     )
 }
 
-pub fn build_map_meshes(map: &TileMap) -> HashMap<TileType, Mesh> {
+/// One mesh per tile type present on the map. Nothing in the live terrain uses these;
+/// build them on demand, e.g. to view a single layer in isolation.
+pub fn build_map_meshes(map: &TileMap, normals: TopNormals) -> HashMap<TileType, Mesh> {
     let mut buffers: HashMap<TileType, MeshBuffers> = HashMap::new();
-    populate_mesh_buffers(map, Some(&mut buffers), None);
+    let corner_cache = compute_corner_cache(map);
+    populate_mesh_buffers(
+        &MeshSource::whole(map, &corner_cache),
+        TileRect::full(map),
+        normals,
+        Some(&mut buffers),
+        None,
+    );
     buffers
         .into_iter()
         .map(|(tile_type, buffer)| (tile_type, buffer.into_mesh()))
         .collect()
 }
 
-pub fn build_combined_mesh(map: &TileMap) -> Mesh {
+/// The combined mesh split into [`CHUNK_SIZE`] chunks, with each chunk's grid coordinates.
+pub fn build_chunk_meshes(map: &TileMap, normals: TopNormals) -> Vec<(UVec2, Mesh)> {
+    let mut cache = TerrainMeshCache::new(normals);
+    cache.update(map, None);
+    (0..cache.chunk_count())
+        .map(|index| (cache.chunk_coords(index), cache.chunk_mesh(index)))
+        .collect()
+}
+
+pub fn build_combined_mesh(map: &TileMap, normals: TopNormals) -> Mesh {
     let mut buffer = MeshBuffers::with_tile_types();
-    populate_mesh_buffers(map, None, Some(&mut buffer));
+    let corner_cache = compute_corner_cache(map);
+    populate_mesh_buffers(
+        &MeshSource::whole(map, &corner_cache),
+        TileRect::full(map),
+        normals,
+        None,
+        Some(&mut buffer),
+    );
     buffer.into_mesh()
 }
 
-fn populate_mesh_buffers(
-    map: &TileMap,
-    mut per_type: Option<&mut HashMap<TileType, MeshBuffers>>,
-    mut combined: Option<&mut MeshBuffers>,
-) {
-    if map.width == 0 || map.height == 0 {
-        return;
+/// The combined terrain geometry kept per [`CHUNK_SIZE`] block, so an edit only re-meshes the
+/// chunks whose tiles or side faces it can change.
+#[derive(Default)]
+pub struct TerrainMeshCache {
+    normals: TopNormals,
+    width: u32,
+    height: u32,
+    chunks: Vec<MeshBuffers>,
+}
+
+/// Everything needed to re-mesh some chunks, detached from the cache so it can run
+/// on another thread. Apply the result with [`TerrainMeshCache::apply`].
+pub struct ChunkBuildJob {
+    width: u32,
+    height: u32,
+    normals: TopNormals,
+    chunks: Vec<ChunkTiles>,
+}
+
+/// A copy of one chunk's tiles plus the two-tile border that its corners, side faces
+/// and blend masks read.
+struct ChunkTiles {
+    index: usize,
+    /// The chunk's tiles, in the coordinates of `tiles`.
+    rect: TileRect,
+    /// Where `tiles` starts in the full map.
+    origin: UVec2,
+    tiles: TileMap,
+}
+
+/// Chunks re-meshed by a [`ChunkBuildJob`].
+pub struct BuiltChunks {
+    width: u32,
+    height: u32,
+    chunks: Vec<(usize, MeshBuffers)>,
+}
+
+impl TerrainMeshCache {
+    pub fn new(normals: TopNormals) -> Self {
+        Self {
+            normals,
+            ..Default::default()
+        }
+    }
+
+    /// Re-meshes the chunks around the `dirty` tiles, or the whole map when `dirty`
+    /// is `None` or the map size changed since the last update. Returns the indices
+    /// of the chunks that were rebuilt.
+    pub fn update(&mut self, map: &TileMap, dirty: Option<TileRect>) -> Vec<usize> {
+        let chunks = self.plan(map, dirty);
+        let built = self.build_job(map, chunks).run();
+        self.apply(built)
+    }
+
+    /// Indices of the chunks an edit of the `dirty` tiles affects: every chunk when
+    /// `dirty` is `None` or the map no longer matches the cached size.
+    pub fn plan(&self, map: &TileMap, dirty: Option<TileRect>) -> Vec<usize> {
+        let resized = self.width != map.width || self.height != map.height;
+        let dirty = match dirty {
+            Some(rect) if !resized => rect,
+            _ => return (0..chunk_count(map.width, map.height)).collect(),
+        };
+        if dirty.is_empty() {
+            return Vec::new();
+        }
+
+        // Ramp corners follow their neighbours' elevation, and each tile's side faces
+        // and blend mask follow its neighbours' corners.
+        let affected = dirty.grow(2, map);
+        let chunks_x = map.width.div_ceil(CHUNK_SIZE);
+        let mut chunks = Vec::new();
+        for cy in affected.y0 / CHUNK_SIZE..affected.y1.div_ceil(CHUNK_SIZE) {
+            for cx in affected.x0 / CHUNK_SIZE..affected.x1.div_ceil(CHUNK_SIZE) {
+                chunks.push((cy * chunks_x + cx) as usize);
+            }
+        }
+        chunks
+    }
+
+    /// Copies the tiles around `chunks` for re-meshing them, e.g. on the
+    /// `AsyncComputeTaskPool`.
+    pub fn build_job(&self, map: &TileMap, mut chunks: Vec<usize>) -> ChunkBuildJob {
+        chunks.sort_unstable();
+        chunks.dedup();
+        chunks.retain(|&index| index < chunk_count(map.width, map.height));
+        let chunks = chunks
+            .into_iter()
+            .map(|index| {
+                let rect = chunk_rect(map.width, map.height, index);
+                let window = rect.grow(2, map);
+                ChunkTiles {
+                    index,
+                    rect: TileRect {
+                        x0: rect.x0 - window.x0,
+                        y0: rect.y0 - window.y0,
+                        x1: rect.x1 - window.x0,
+                        y1: rect.y1 - window.y0,
+                    },
+                    origin: UVec2::new(window.x0, window.y0),
+                    tiles: map.crop(window),
+                }
+            })
+            .collect();
+        ChunkBuildJob {
+            width: map.width,
+            height: map.height,
+            normals: self.normals,
+            chunks,
+        }
+    }
+
+    /// Swaps in freshly built chunks and returns their indices. A result for a
+    /// different map size replaces the whole chunk grid.
+    pub fn apply(&mut self, built: BuiltChunks) -> Vec<usize> {
+        if self.width != built.width || self.height != built.height {
+            self.width = built.width;
+            self.height = built.height;
+            self.chunks = Vec::new();
+            self.chunks
+                .resize_with(chunk_count(built.width, built.height), Default::default);
+        }
+        built
+            .chunks
+            .into_iter()
+            .map(|(index, chunk)| {
+                self.chunks[index] = chunk;
+                index
+            })
+            .collect()
+    }
+
+    pub fn chunk_count(&self) -> usize {
+        self.chunks.len()
+    }
+
+    /// Grid coordinates of a chunk; chunk `(cx, cy)` starts at tile `(cx, cy) * CHUNK_SIZE`.
+    pub fn chunk_coords(&self, index: usize) -> UVec2 {
+        let chunks_x = self.width.div_ceil(CHUNK_SIZE).max(1);
+        UVec2::new(index as u32 % chunks_x, index as u32 / chunks_x)
+    }
+
+    /// The combined mesh of a single chunk.
+    pub fn chunk_mesh(&self, index: usize) -> Mesh {
+        let mut buffer = MeshBuffers::with_tile_types();
+        buffer.append(&self.chunks[index]);
+        buffer.into_mesh()
+    }
+}
+
+impl ChunkBuildJob {
+    /// The chunks this job re-meshes, so a cancelled job's work can be folded into the next.
+    pub fn chunks(&self) -> impl Iterator<Item = usize> + '_ {
+        self.chunks.iter().map(|chunk| chunk.index)
     }
 
+    pub fn run(self) -> BuiltChunks {
+        let chunks = self
+            .chunks
+            .iter()
+            .map(|chunk| chunk.build(self.normals))
+            .collect();
+        BuiltChunks {
+            width: self.width,
+            height: self.height,
+            chunks,
+        }
+    }
+
+    /// [`Self::run`] for a task pool: yields after each chunk, so dropping the task
+    /// stops the work there instead of finishing a superseded build.
+    pub async fn run_async(self) -> BuiltChunks {
+        let mut chunks = Vec::with_capacity(self.chunks.len());
+        for chunk in &self.chunks {
+            chunks.push(chunk.build(self.normals));
+            futures_lite::future::yield_now().await;
+        }
+        BuiltChunks {
+            width: self.width,
+            height: self.height,
+            chunks,
+        }
+    }
+}
+
+impl ChunkTiles {
+    fn build(&self, normals: TopNormals) -> (usize, MeshBuffers) {
+        let tiles = &self.tiles;
+        // Meshing reads corners one tile around the chunk; the outer border is
+        // only there for those corners' ramps.
+        let mut corner_cache = vec![[0.0f32; 4]; tiles.tiles.len()];
+        for (x, y) in self.rect.grow(1, tiles).tiles() {
+            corner_cache[tiles.idx(x, y)] = tile_corner_heights(tiles, x, y);
+        }
+
+        let source = MeshSource {
+            map: tiles,
+            corners: &corner_cache,
+            origin: self.origin,
+        };
+        let mut buffers = MeshBuffers::with_tile_types();
+        populate_mesh_buffers(&source, self.rect, normals, None, Some(&mut buffers));
+        buffers.finish();
+        (self.index, buffers)
+    }
+}
+
+fn chunk_count(width: u32, height: u32) -> usize {
+    (width.div_ceil(CHUNK_SIZE) * height.div_ceil(CHUNK_SIZE)) as usize
+}
+
+/// The tiles of chunk `index` on a `width` x `height` map.
+fn chunk_rect(width: u32, height: u32, index: usize) -> TileRect {
+    let chunks_x = width.div_ceil(CHUNK_SIZE);
+    let (cx, cy) = (index as u32 % chunks_x, index as u32 / chunks_x);
+    TileRect {
+        x0: cx * CHUNK_SIZE,
+        y0: cy * CHUNK_SIZE,
+        x1: ((cx + 1) * CHUNK_SIZE).min(width),
+        y1: ((cy + 1) * CHUNK_SIZE).min(height),
+    }
+}
+
+fn compute_corner_cache(map: &TileMap) -> Vec<[f32; 4]> {
     let mut corner_cache = vec![[0.0f32; 4]; (map.width * map.height) as usize];
     for y in 0..map.height {
         for x in 0..map.width {
@@ -99,40 +356,76 @@ fn populate_mesh_buffers(
             corner_cache[idx] = tile_corner_heights(map, x, y);
         }
     }
+    corner_cache
+}
 
-    for y in 0..map.height {
-        for x in 0..map.width {
-            if let Some(buffers) = per_type.as_mut() {
-                let tile_type = map.get(x, y).tile_type;
-                let buffer = buffers.entry(tile_type).or_default();
-                append_tile_geometry(map, &corner_cache, x, y, buffer, None);
-            }
+/// Tiles and corner heights to mesh. `map` may be a window cut out of a larger map,
+/// starting at tile `origin` there; geometry is placed at full-map positions.
+#[derive(Clone, Copy)]
+struct MeshSource<'a> {
+    map: &'a TileMap,
+    corners: &'a [[f32; 4]],
+    origin: UVec2,
+}
 
-            if let Some(combined_buffer) = combined.as_mut() {
-                let tile_layer = map.get(x, y).tile_type.as_index() as f32;
+impl<'a> MeshSource<'a> {
+    fn whole(map: &'a TileMap, corners: &'a [[f32; 4]]) -> Self {
+        Self {
+            map,
+            corners,
+            origin: UVec2::ZERO,
+        }
+    }
+}
 
-                // dbg!(map.get(x, y).tile_type);
+fn populate_mesh_buffers(
+    source: &MeshSource,
+    rect: TileRect,
+    normals: TopNormals,
+    mut per_type: Option<&mut HashMap<TileType, MeshBuffers>>,
+    mut combined: Option<&mut MeshBuffers>,
+) {
+    let map = source.map;
+    if map.width == 0 || map.height == 0 {
+        return;
+    }
 
-                append_tile_geometry(map, &corner_cache, x, y, combined_buffer, Some(tile_layer));
-            }
+    for (x, y) in rect.tiles() {
+        if let Some(buffers) = per_type.as_mut() {
+            let tile_type = map.get(x, y).tile_type;
+            let buffer = buffers.entry(tile_type).or_default();
+            append_tile_geometry(source, x, y, normals, buffer, None);
+        }
+
+        if let Some(combined_buffer) = combined.as_mut() {
+            let tile_layer = map.get(x, y).tile_type.as_index() as f32;
+
+            // dbg!(map.get(x, y).tile_type);
+
+            append_tile_geometry(source, x, y, normals, combined_buffer, Some(tile_layer));
         }
     }
 }
 
 fn append_tile_geometry(
-    map: &TileMap,
-    corner_cache: &[[f32; 4]],
+    source: &MeshSource,
     x: u32,
     y: u32,
+    normals: TopNormals,
     buffer: &mut MeshBuffers,
     tile_layer: Option<f32>,
 ) {
+    let MeshSource {
+        map,
+        corners: corner_cache,
+        origin,
+    } = *source;
     let idx = map.idx(x, y);
     let corners = corner_cache[idx];
     let tile_kind = map.get(x, y).kind;
-    let x0 = x as f32 * TILE_SIZE;
+    let x0 = (origin.x + x) as f32 * TILE_SIZE;
     let x1 = x0 + TILE_SIZE;
-    let z0 = y as f32 * TILE_SIZE;
+    let z0 = (origin.y + y) as f32 * TILE_SIZE;
     let z1 = z0 + TILE_SIZE;
 
     let nw = Vec3::new(x0, corners[CORNER_NW], z0);
@@ -148,11 +441,25 @@ fn append_tile_geometry(
         None
     };
 
+    let top_normals = match normals {
+        TopNormals::Flat => None,
+        TopNormals::Smooth => Some(
+            [
+                (x, y, nw),
+                (x, y + 1, sw),
+                (x + 1, y + 1, se),
+                (x + 1, y, ne),
+            ]
+            .map(|(corner_x, corner_y, position)| {
+                smooth_top_normal(map, corner_cache, corner_x, corner_y, position.y)
+            }),
+        ),
+    };
     buffer.push_quad(
         [nw, sw, se, ne],
-        [[0.0, 0.0]; 4],
-        tile_layer,
-        top_height,
+        [nw, sw, se, ne].map(top_uv),
+        top_normals,
+        tile_layer.map(|layer| [layer, top_height]),
         top_color_info,
     );
 
@@ -321,7 +628,7 @@ fn append_tile_geometry(
     );
 }
 
-fn find_ramp_target(map: &TileMap, x: u32, y: u32, base: f32) -> Option<(RampDirection, f32)> {
+pub fn find_ramp_target(map: &TileMap, x: u32, y: u32, base: f32) -> Option<(RampDirection, f32)> {
     let mut result: Option<(RampDirection, f32)> = None;
     for dir in RampDirection::ALL {
         if let Some(height) = ramp_neighbor_height(map, x, y, dir, base) {
@@ -336,7 +643,7 @@ fn find_ramp_target(map: &TileMap, x: u32, y: u32, base: f32) -> Option<(RampDir
     result
 }
 
-fn ramp_neighbor_height(
+pub fn ramp_neighbor_height(
     map: &TileMap,
     x: u32,
     y: u32,
@@ -366,7 +673,9 @@ struct MeshBuffers {
     tile_layers: Option<Vec<[f32; 2]>>,
     colors: Option<Vec<[f32; 4]>>,
     indices: Vec<u32>,
-    next_index: u32,
+    /// Vertices pushed so far keyed by their attribute bits, so faces that share a
+    /// vertex reference it instead of repeating it.
+    vertex_lookup: HashMap<[u32; 14], u32>,
 }
 
 impl MeshBuffers {
@@ -378,27 +687,102 @@ impl MeshBuffers {
         }
     }
 
+    /// Appends another buffer's geometry, offsetting its indices. Vertices are not
+    /// shared across the two buffers.
+    fn append(&mut self, other: &MeshBuffers) {
+        let offset = self.positions.len() as u32;
+        self.positions.extend_from_slice(&other.positions);
+        self.normals.extend_from_slice(&other.normals);
+        self.uvs.extend_from_slice(&other.uvs);
+        if let (Some(layers), Some(other_layers)) =
+            (self.tile_layers.as_mut(), other.tile_layers.as_ref())
+        {
+            layers.extend_from_slice(other_layers);
+        }
+        if let (Some(colors), Some(other_colors)) = (self.colors.as_mut(), other.colors.as_ref()) {
+            colors.extend_from_slice(other_colors);
+        }
+        self.indices
+            .extend(other.indices.iter().map(|index| index + offset));
+    }
+
+    /// Drops the vertex lookup once no more faces will be pushed, e.g. for cached chunks.
+    fn finish(&mut self) {
+        self.vertex_lookup = HashMap::new();
+    }
+
+    fn push_vertex(
+        &mut self,
+        position: Vec3,
+        normal: Vec3,
+        uv: [f32; 2],
+        tile_info: [f32; 2],
+        color: [f32; 4],
+    ) -> u32 {
+        let mut key = [0u32; 14];
+        let values = position
+            .to_array()
+            .into_iter()
+            .chain(normal.to_array())
+            .chain(uv)
+            .chain(tile_info)
+            .chain(color);
+        for (slot, value) in key.iter_mut().zip(values) {
+            *slot = value.to_bits();
+        }
+
+        if let Some(&index) = self.vertex_lookup.get(&key) {
+            return index;
+        }
+        let index = self.positions.len() as u32;
+        self.positions.push(position.to_array());
+        self.normals.push(normal.to_array());
+        self.uvs.push(uv);
+        if let Some(layers) = self.tile_layers.as_mut() {
+            layers.push(tile_info);
+        }
+        if let Some(colors) = self.colors.as_mut() {
+            colors.push(color);
+        }
+        self.vertex_lookup.insert(key, index);
+        index
+    }
+
+    /// Pushes the triangles `(0, 1, 2)` and `(0, 2, 3)` of a quad. Without `normals`
+    /// each triangle is shaded flat, sharing one normal when the quad is planar.
     fn push_quad(
         &mut self,
         verts: [Vec3; 4],
         tex: [[f32; 2]; 4],
-        tile_layer: Option<f32>,
-        seam_height: f32,
-        bottom_layer: Option<[f32; 4]>,
+        normals: Option<[Vec3; 4]>,
+        tile_info: Option<[f32; 2]>,
+        color_info: Option<[f32; 4]>,
     ) {
-        push_quad(
-            &mut self.positions,
-            &mut self.normals,
-            &mut self.uvs,
-            self.tile_layers.as_mut(),
-            self.colors.as_mut(),
-            &mut self.indices,
-            &mut self.next_index,
-            verts,
-            tex,
-            tile_layer.map(|layer| [layer, seam_height]),
-            bottom_layer,
-        );
+        const PLANAR_EPSILON: f32 = 1e-6;
+
+        let tile_info = tile_info.unwrap_or([0.0, 0.0]);
+        let color = color_info.unwrap_or([-1.0, 0.0, 0.0, 0.0]);
+        let triangles = [[0, 1, 2], [0, 2, 3]];
+        let faces = triangles.map(|[a, b, c]| (verts[b] - verts[a]).cross(verts[c] - verts[a]));
+        let [first, second] = faces.map(Vec3::normalize_or_zero);
+        let flat = if first.dot(second) > 1.0 - PLANAR_EPSILON {
+            let shared = (faces[0] + faces[1]).normalize_or_zero();
+            [shared, shared]
+        } else {
+            [first, second]
+        };
+
+        for ((corners, face), flat_normal) in triangles.into_iter().zip(faces).zip(flat) {
+            // Zero-area triangles, such as the tip of a wall that tapers to a point, are invisible.
+            if face.length_squared() <= f32::EPSILON {
+                continue;
+            }
+            for corner in corners {
+                let normal = normals.map_or(flat_normal, |normals| normals[corner]);
+                let index = self.push_vertex(verts[corner], normal, tex[corner], tile_info, color);
+                self.indices.push(index);
+            }
+        }
     }
 
     fn add_side_face(
@@ -413,23 +797,28 @@ impl MeshBuffers {
         bottom_info: Option<[f32; 4]>,
         force_cliff: bool,
     ) {
-        add_side_face(
-            &mut self.positions,
-            &mut self.normals,
-            &mut self.uvs,
-            self.tile_layers.as_mut(),
-            self.colors.as_mut(),
-            &mut self.indices,
-            &mut self.next_index,
-            top_a,
-            top_b,
-            bottom_a,
-            bottom_b,
-            direction,
-            tile_layer,
-            seam_height,
-            bottom_info,
-            force_cliff,
+        const EPS: f32 = 1e-4;
+        if (top_a.y - bottom_a.y).abs() < EPS && (top_b.y - bottom_b.y).abs() < EPS {
+            return;
+        }
+
+        let verts = [top_a, top_b, bottom_b, bottom_a];
+        let tex = verts.map(|position| wall_uv(position, direction));
+
+        let mut color_info = bottom_info;
+        if let Some(info) = color_info.as_mut() {
+            info[2] = if force_cliff { 1.0 } else { 0.0 };
+        } else if force_cliff {
+            color_info = Some([-1.0, 0.0, 1.0, 0.0]);
+        }
+
+        // Walls always keep their own flat normals, so cliff edges stay hard.
+        self.push_quad(
+            verts,
+            tex,
+            None,
+            tile_layer.map(|layer| [layer, seam_height]),
+            color_info,
         );
     }
 
@@ -455,67 +844,70 @@ impl MeshBuffers {
     }
 }
 
-fn push_quad(
-    positions: &mut Vec<[f32; 3]>,
-    normals: &mut Vec<[f32; 3]>,
-    uvs: &mut Vec<[f32; 2]>,
-    tile_layers: Option<&mut Vec<[f32; 2]>>,
-    colors: Option<&mut Vec<[f32; 4]>>,
-    indices: &mut Vec<u32>,
-    next_index: &mut u32,
-    verts: [Vec3; 4],
-    tex: [[f32; 2]; 4],
-    tile_info: Option<[f32; 2]>,
-    color_info: Option<[f32; 4]>,
-) {
-    push_triangle(
-        positions, normals, uvs, indices, next_index, verts[0], verts[1], verts[2], tex[0], tex[1],
-        tex[2],
-    );
-    push_triangle(
-        positions, normals, uvs, indices, next_index, verts[0], verts[2], verts[3], tex[0], tex[2],
-        tex[3],
-    );
+/// Angle-weighted normal of the tile tops that meet at grid corner `(corner_x, corner_y)`
+/// at `height`. Tops at another height there are across a cliff and don't contribute.
+fn smooth_top_normal(
+    map: &TileMap,
+    corner_cache: &[[f32; 4]],
+    corner_x: u32,
+    corner_y: u32,
+    height: f32,
+) -> Vec3 {
+    const HEIGHT_EPSILON: f32 = 1e-4;
+    const TRIANGLES: [[usize; 3]; 2] = [
+        [CORNER_NW, CORNER_SW, CORNER_SE],
+        [CORNER_NW, CORNER_SE, CORNER_NE],
+    ];
 
-    if let Some(layers) = tile_layers {
-        for _ in 0..6 {
-            layers.push(tile_info.unwrap_or([0.0, 0.0]));
+    let mut sum = Vec3::ZERO;
+    for (dx, dy, corner) in [
+        (0, 0, CORNER_NW),
+        (1, 0, CORNER_NE),
+        (0, 1, CORNER_SW),
+        (1, 1, CORNER_SE),
+    ] {
+        let (Some(x), Some(y)) = (corner_x.checked_sub(dx), corner_y.checked_sub(dy)) else {
+            continue;
+        };
+        if x >= map.width || y >= map.height {
+            continue;
         }
-    }
+        let heights = corner_cache[map.idx(x, y)];
+        if (heights[corner] - height).abs() > HEIGHT_EPSILON {
+            continue;
+        }
+
+        let (x0, z0) = (x as f32 * TILE_SIZE, y as f32 * TILE_SIZE);
+        let mut verts = [Vec3::ZERO; 4];
+        verts[CORNER_NW] = Vec3::new(x0, heights[CORNER_NW], z0);
+        verts[CORNER_NE] = Vec3::new(x0 + TILE_SIZE, heights[CORNER_NE], z0);
+        verts[CORNER_SW] = Vec3::new(x0, heights[CORNER_SW], z0 + TILE_SIZE);
+        verts[CORNER_SE] = Vec3::new(x0 + TILE_SIZE, heights[CORNER_SE], z0 + TILE_SIZE);
 
-    if let Some(colors) = colors {
-        let value = color_info.unwrap_or([-1.0, 0.0, 0.0, 0.0]);
-        for _ in 0..6 {
-            colors.push(value);
+        for triangle in TRIANGLES {
+            let Some(at) = triangle.iter().position(|&index| index == corner) else {
+                continue;
+            };
+            let origin = verts[triangle[at]];
+            let to_next = verts[triangle[(at + 1) % 3]] - origin;
+            let to_prev = verts[triangle[(at + 2) % 3]] - origin;
+            sum += to_next.cross(to_prev).normalize_or_zero() * to_next.angle_between(to_prev);
         }
     }
+    sum.try_normalize().unwrap_or(Vec3::Y)
 }
 
-fn push_triangle(
-    positions: &mut Vec<[f32; 3]>,
-    normals: &mut Vec<[f32; 3]>,
-    uvs: &mut Vec<[f32; 2]>,
-    indices: &mut Vec<u32>,
-    next_index: &mut u32,
-    a: Vec3,
-    b: Vec3,
-    c: Vec3,
-    ta: [f32; 2],
-    tb: [f32; 2],
-    tc: [f32; 2],
-) {
-    let normal = (b - a).cross(c - a).normalize_or_zero();
-    positions.push(a.to_array());
-    positions.push(b.to_array());
-    positions.push(c.to_array());
-    normals.push(normal.to_array());
-    normals.push(normal.to_array());
-    normals.push(normal.to_array());
-    uvs.push(ta);
-    uvs.push(tb);
-    uvs.push(tc);
-    indices.extend_from_slice(&[*next_index, *next_index + 1, *next_index + 2]);
-    *next_index += 3;
+/// World-space planar UV of a tile top: the same XZ projection the terrain shader uses.
+fn top_uv(position: Vec3) -> [f32; 2] {
+    [position.x * UV_SCALE, position.z * UV_SCALE]
+}
+
+/// UV of a cliff wall facing `direction`: `u` runs left to right as seen from outside
+/// the wall and `v` runs down, so textures stay upright and unmirrored.
+fn wall_uv(position: Vec3, direction: RampDirection) -> [f32; 2] {
+    let (dx, dz) = direction.offset();
+    let along = dz as f32 * position.x - dx as f32 * position.z;
+    [along * UV_SCALE, -position.y * UV_SCALE]
 }
 
 fn tile_top_blend_mask(
@@ -528,33 +920,25 @@ fn tile_top_blend_mask(
     const HEIGHT_EPSILON: f32 = 0.01;
 
     let mut mask_bits: u32 = 0;
+    // A clear bit makes the shader pull its splat sample back to the tile centre on
+    // that side. On the map border the clamp-to-edge sampler already returns the
+    // border texel there, so the bit makes no visible difference; setting it keeps
+    // border tops' attributes equal to their neighbours', so exports can merge them.
+    let level_with = |nx: u32, ny: u32| {
+        (top_height - max_corner_height(corner_cache[map.idx(nx, ny)])).abs() < HEIGHT_EPSILON
+    };
 
-    if y > 0 {
-        let neighbor = corner_cache[map.idx(x, y - 1)];
-        if (top_height - max_corner_height(neighbor)).abs() < HEIGHT_EPSILON {
-            mask_bits |= 0b0001;
-        }
+    if y == 0 || level_with(x, y - 1) {
+        mask_bits |= 0b0001;
     }
-
-    if y + 1 < map.height {
-        let neighbor = corner_cache[map.idx(x, y + 1)];
-        if (top_height - max_corner_height(neighbor)).abs() < HEIGHT_EPSILON {
-            mask_bits |= 0b0010;
-        }
+    if y + 1 >= map.height || level_with(x, y + 1) {
+        mask_bits |= 0b0010;
     }
-
-    if x > 0 {
-        let neighbor = corner_cache[map.idx(x - 1, y)];
-        if (top_height - max_corner_height(neighbor)).abs() < HEIGHT_EPSILON {
-            mask_bits |= 0b0100;
-        }
+    if x == 0 || level_with(x - 1, y) {
+        mask_bits |= 0b0100;
     }
-
-    if x + 1 < map.width {
-        let neighbor = corner_cache[map.idx(x + 1, y)];
-        if (top_height - max_corner_height(neighbor)).abs() < HEIGHT_EPSILON {
-            mask_bits |= 0b1000;
-        }
+    if x + 1 >= map.width || level_with(x + 1, y) {
+        mask_bits |= 0b1000;
     }
 
     [-2.0, mask_bits as f32, 0.0, 0.0]
@@ -566,58 +950,6 @@ fn max_corner_height(corners: [f32; 4]) -> f32 {
         .fold(f32::NEG_INFINITY, |acc, value| acc.max(value))
 }
 
-fn add_side_face(
-    positions: &mut Vec<[f32; 3]>,
-    normals: &mut Vec<[f32; 3]>,
-    uvs: &mut Vec<[f32; 2]>,
-    tile_layers: Option<&mut Vec<[f32; 2]>>,
-    colors: Option<&mut Vec<[f32; 4]>>,
-    indices: &mut Vec<u32>,
-    next_index: &mut u32,
-    top_a: Vec3,
-    top_b: Vec3,
-    bottom_a: Vec3,
-    bottom_b: Vec3,
-    direction: RampDirection,
-    tile_layer: Option<f32>,
-    seam_height: f32,
-    bottom_info: Option<[f32; 4]>,
-    force_cliff: bool,
-) {
-    const EPS: f32 = 1e-4;
-    if (top_a.y - bottom_a.y).abs() < EPS && (top_b.y - bottom_b.y).abs() < EPS {
-        return;
-    }
-
-    let (verts, tex) = match direction {
-        RampDirection::North => ([top_a, top_b, bottom_b, bottom_a], [[0.0, 0.0]; 4]),
-        RampDirection::South => ([top_a, top_b, bottom_b, bottom_a], [[0.0, 0.0]; 4]),
-        RampDirection::West => ([top_a, top_b, bottom_b, bottom_a], [[0.0, 0.0]; 4]),
-        RampDirection::East => ([top_a, top_b, bottom_b, bottom_a], [[0.0, 0.0]; 4]),
-    };
-
-    let mut color_info = bottom_info;
-    if let Some(info) = color_info.as_mut() {
-        info[2] = if force_cliff { 1.0 } else { 0.0 };
-    } else if force_cliff {
-        color_info = Some([-1.0, 0.0, 1.0, 0.0]);
-    }
-
-    push_quad(
-        positions,
-        normals,
-        uvs,
-        tile_layers,
-        colors,
-        indices,
-        next_index,
-        verts,
-        tex,
-        tile_layer.map(|layer| [layer, seam_height]),
-        color_info,
-    );
-}
-
 fn should_force_cliff_face(
     tile_kind: TileKind,
     neighbor_kind: Option<TileKind>,
@@ -646,52 +978,100 @@ pub mod splatmap {
     const CHANNELS: usize = 4;
 
     pub fn create(map: &TileMap) -> Image {
-        let extent = extent_from_map(map);
-        let mut image = Image::new_fill(
-            extent,
+        let mut image = Image::default();
+        write(map, &mut image);
+        image
+    }
+
+    /// Wraps RGBA8 splat weights (e.g. a packaged `splatmap.png`) with the same
+    /// format and sampler as [`create`].
+    pub fn from_rgba8(width: u32, height: u32, data: Vec<u8>) -> Image {
+        let mut image = Image::new(
+            Extent3d {
+                width,
+                height,
+                depth_or_array_layers: 1,
+            },
             TextureDimension::D2,
-            &[0u8; CHANNELS],
+            data,
             TextureFormat::Rgba8Unorm,
             RenderAssetUsages::default(),
         );
         configure_image(&mut image);
-        write(map, &mut image);
         image
     }
 
     pub fn write(map: &TileMap, image: &mut Image) {
-        let extent = extent_from_map(map);
-        if image.texture_descriptor.size != extent
-            || image.texture_descriptor.format != TextureFormat::Rgba8Unorm
-        {
-            *image = create(map);
-            return;
-        }
+        SplatPatch::new(map, TileRect::full(map)).apply(image);
+    }
 
-        configure_image(image);
+    /// The splat pixels of some tiles, so an edit can be carried to the image
+    /// without copying the whole texture.
+    pub struct SplatPatch {
+        extent: Extent3d,
+        rect: TileRect,
+        pixels: Vec<u8>,
+    }
 
-        let width = extent.width as usize;
-        let required_len = width * (extent.height as usize) * CHANNELS;
-        if image.data.len() != required_len {
-            image.data.resize(required_len, 0);
+    impl SplatPatch {
+        pub fn new(map: &TileMap, rect: TileRect) -> Self {
+            let rect = rect.intersect(TileRect::full(map));
+            let pixels = rect
+                .tiles()
+                .flat_map(|(x, y)| {
+                    let mut pixel = [0u8; CHANNELS];
+                    let layer = map.get(x, y).tile_type.as_index();
+                    if layer < CHANNELS {
+                        pixel[layer] = 255;
+                    }
+                    pixel
+                })
+                .collect();
+            Self {
+                extent: extent_from_map(map),
+                rect,
+                pixels,
+            }
         }
 
-        if map.width == 0 || map.height == 0 {
-            image.data.fill(0);
-            return;
+        /// Whether `image` already has this map's size and format, so a patch of
+        /// part of the map is enough to bring it up to date.
+        pub fn fits(map: &TileMap, image: &Image) -> bool {
+            image.texture_descriptor.size == extent_from_map(map)
+                && image.texture_descriptor.format == TextureFormat::Rgba8Unorm
         }
 
-        for y in 0..map.height as usize {
-            for x in 0..map.width as usize {
-                let mut pixel = [0u8; CHANNELS];
-                let tile = map.get(x as u32, y as u32);
-                let layer = tile.tile_type.as_index();
-                if layer < CHANNELS {
-                    pixel[layer] = 255;
-                }
+        /// Writes the pixels into `image`. An image of another size or format is
+        /// replaced by a blank one first, so patch the whole map in that case.
+        pub fn apply(&self, image: &mut Image) {
+            if image.texture_descriptor.size != self.extent
+                || image.texture_descriptor.format != TextureFormat::Rgba8Unorm
+            {
+                *image = Image::new_fill(
+                    self.extent,
+                    TextureDimension::D2,
+                    &[0u8; CHANNELS],
+                    TextureFormat::Rgba8Unorm,
+                    RenderAssetUsages::default(),
+                );
+            }
+
+            configure_image(image);
 
-                let idx = (y * width + x) * CHANNELS;
-                image.data[idx..idx + CHANNELS].copy_from_slice(&pixel);
+            let width = self.extent.width as usize;
+            let required_len = width * (self.extent.height as usize) * CHANNELS;
+            if image.data.len() != required_len {
+                image.data.resize(required_len, 0);
+            }
+
+            let row_len = (self.rect.x1.saturating_sub(self.rect.x0)) as usize * CHANNELS;
+            if row_len == 0 {
+                return;
+            }
+            for (row, y) in (self.rect.y0..self.rect.y1).enumerate() {
+                let start = (y as usize * width + self.rect.x0 as usize) * CHANNELS;
+                image.data[start..start + row_len]
+                    .copy_from_slice(&self.pixels[row * row_len..(row + 1) * row_len]);
             }
         }
     }
@@ -717,3 +1097,122 @@ pub mod splatmap {
         });
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Three by two chunks, the last column and row partial, with a cliff
+    /// plateau in the first chunk.
+    fn sample_map() -> TileMap {
+        let mut map = TileMap::new(CHUNK_SIZE * 2 + 6, CHUNK_SIZE + 8);
+        for y in 8..14 {
+            for x in 12..18 {
+                let index = map.idx(x, y);
+                map.tiles[index].elevation = 2;
+            }
+        }
+        map
+    }
+
+    fn set_tile(map: &mut TileMap, x: u32, y: u32, elevation: i8, ramp: Option<RampDirection>) {
+        let index = map.idx(x, y);
+        let tile = &mut map.tiles[index];
+        tile.elevation = elevation;
+        tile.kind = if ramp.is_some() {
+            TileKind::Ramp
+        } else {
+            TileKind::Floor
+        };
+        tile.ramp_direction = ramp;
+    }
+
+    /// Chunks meshed against corners computed for the whole map, independent of
+    /// the regions `ChunkBuildJob` fills in.
+    fn reference_chunks(map: &TileMap, normals: TopNormals) -> Vec<MeshBuffers> {
+        let corner_cache = compute_corner_cache(map);
+        let source = MeshSource::whole(map, &corner_cache);
+        (0..chunk_count(map.width, map.height))
+            .map(|index| {
+                let rect = chunk_rect(map.width, map.height, index);
+                let mut chunk = MeshBuffers::with_tile_types();
+                populate_mesh_buffers(&source, rect, normals, None, Some(&mut chunk));
+                chunk.finish();
+                chunk
+            })
+            .collect()
+    }
+
+    fn assert_same_chunks(actual: &[MeshBuffers], expected: &[MeshBuffers]) {
+        assert_eq!(actual.len(), expected.len());
+        for (index, (a, b)) in actual.iter().zip(expected).enumerate() {
+            assert_eq!(a.positions, b.positions, "positions of chunk {index}");
+            assert_eq!(a.normals, b.normals, "normals of chunk {index}");
+            assert_eq!(a.indices, b.indices, "indices of chunk {index}");
+        }
+    }
+
+    #[test]
+    fn splat_patch_matches_full_rewrite() {
+        let mut map = sample_map();
+        let mut image = splatmap::create(&map);
+        let rect = TileRect {
+            x0: CHUNK_SIZE - 2,
+            y0: 3,
+            x1: CHUNK_SIZE + 3,
+            y1: 6,
+        };
+        for (x, y) in rect.tiles() {
+            let index = map.idx(x, y);
+            map.tiles[index].tile_type = TileType::Rock;
+        }
+
+        assert!(splatmap::SplatPatch::fits(&map, &image));
+        splatmap::SplatPatch::new(&map, rect).apply(&mut image);
+        assert_eq!(image.data, splatmap::create(&map).data);
+    }
+
+    #[test]
+    fn incremental_update_matches_full_rebuild() {
+        // (tiles to edit, elevation, ramp direction)
+        let edits = [
+            // A ramp down off the plateau's west cliff.
+            (TileRect::tile(11, 10), 2, Some(RampDirection::West)),
+            // The ramp's lower neighbour rising, which flattens it.
+            (TileRect::tile(10, 10), 2, None),
+            // A tile on the corner shared by four chunks.
+            (TileRect::tile(CHUNK_SIZE - 1, CHUNK_SIZE - 1), 3, None),
+            // A ramp just across a chunk border, pointing back over it.
+            (
+                TileRect::tile(CHUNK_SIZE, CHUNK_SIZE - 1),
+                3,
+                Some(RampDirection::East),
+            ),
+            // A ramp on the map border.
+            (
+                TileRect::tile(CHUNK_SIZE * 2 + 5, 20),
+                1,
+                Some(RampDirection::West),
+            ),
+        ];
+
+        for normals in [TopNormals::Flat, TopNormals::Smooth] {
+            let mut map = sample_map();
+            let mut cache = TerrainMeshCache::new(normals);
+            cache.update(&map, None);
+
+            for (rect, elevation, ramp) in edits {
+                for (x, y) in rect.tiles() {
+                    set_tile(&mut map, x, y, elevation, ramp);
+                }
+                cache.update(&map, Some(rect));
+
+                let mut fresh = TerrainMeshCache::new(normals);
+                fresh.update(&map, None);
+                let reference = reference_chunks(&map, normals);
+                assert_same_chunks(&fresh.chunks, &reference);
+                assert_same_chunks(&cache.chunks, &reference);
+            }
+        }
+    }
+}
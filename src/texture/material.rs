@@ -1,5 +1,5 @@
 use bevy::asset::Asset;
-use bevy::math::Vec2;
+use bevy::math::{UVec2, Vec2};
 use bevy::pbr::{ExtendedMaterial, MaterialExtension, MaterialPipelineKey, StandardMaterial};
 use bevy::prelude::*;
 use bevy::reflect::TypePath;
@@ -11,6 +11,7 @@ use bevy::render::render_resource::{
 };
 use bevy::render::texture::{Image, ImageLoaderSettings};
 
+use super::registry::TerrainTextureArrays;
 use crate::types::TILE_SIZE;
 
 pub type TerrainMaterial = ExtendedMaterial<StandardMaterial, TerrainMaterialExtension>;
@@ -26,8 +27,12 @@ pub struct TerrainMaterialHandles {
 
 const TILE_REPEAT: f32 = 4.0;
 
+/// Texture repeats per world unit; the shader's triplanar projection and the mesh UVs
+/// both use it.
+pub const UV_SCALE: f32 = 1.0 / (TILE_SIZE * TILE_REPEAT);
+
 fn default_uv_scale() -> f32 {
-    1.0 / (TILE_SIZE * TILE_REPEAT)
+    UV_SCALE
 }
 
 fn default_height_uv_scale() -> f32 {
@@ -302,6 +307,11 @@ pub fn fix_roughness_images_on_load(
 }
 
 pub fn create_runtime_material(materials: &mut Assets<TerrainMaterial>) -> Handle<TerrainMaterial> {
+    materials.add(runtime_material())
+}
+
+/// The blended terrain material before any texture arrays are attached.
+pub fn runtime_material() -> TerrainMaterial {
     let base = StandardMaterial {
         base_color_texture: None,
         normal_map_texture: None,
@@ -312,10 +322,38 @@ pub fn create_runtime_material(materials: &mut Assets<TerrainMaterial>) -> Handl
         ..default()
     };
 
-    materials.add(TerrainMaterial {
+    TerrainMaterial {
         base,
         extension: TerrainMaterialExtension::default(),
-    })
+    }
+}
+
+/// Points the terrain shader at its texture arrays and splat map. Both the editor and
+/// the package loader go through here so a map renders the same in either.
+pub fn apply_texture_arrays(
+    extension: &mut TerrainMaterialExtension,
+    arrays: &TerrainTextureArrays,
+    floor_layers: u32,
+    splat_map: &Handle<Image>,
+    map_size: UVec2,
+) {
+    extension.params.layer_count = floor_layers;
+    extension.base_color_array = Some(arrays.base_color.clone());
+    extension.normal_array = arrays.normal.clone();
+    extension.roughness_array = arrays.roughness.clone();
+    extension.splat_map = Some(splat_map.clone());
+
+    extension.params.map_size = Vec2::new(map_size.x as f32, map_size.y as f32);
+    extension.params.tile_size = TILE_SIZE;
+    // The editor mesh already bakes the desired world-space scaling into the
+    // vertex data, so the shader inputs remain at a neutral scale.
+    extension.params.height_uv_scale = 1.0;
+    extension.params.height_world_scale = 1.0;
+    extension.params.cliff_blend_height = 0.2;
+    extension.params.wall_enabled = arrays.wall_layer_index.map(|_| 1u32).unwrap_or(0);
+    extension.params.wall_layer_index = arrays.wall_layer_index.unwrap_or(u32::MAX);
+    extension.params.wall_has_normal = if arrays.wall_has_normal { 1 } else { 0 };
+    extension.params.wall_has_roughness = if arrays.wall_has_roughness { 1 } else { 0 };
 }
 
 pub fn create_texture_array_image(layers: &[&Image]) -> Option<Image> {
@@ -35,6 +35,67 @@ pub struct WallTextureEntry {
     pub roughness_path: Option<String>,
 }
 
+/// A terrain texture the editor registers at startup, as paths below `assets/`.
+pub struct BuiltinTexture {
+    pub tile_type: TileType,
+    pub name: &'static str,
+    pub base_color: &'static str,
+    pub normal: Option<&'static str>,
+    pub roughness: Option<&'static str>,
+    pub dispersion: Option<&'static str>,
+}
+
+pub struct BuiltinWallTexture {
+    pub id: &'static str,
+    pub name: &'static str,
+    pub base_color: &'static str,
+    pub normal: Option<&'static str>,
+    pub roughness: Option<&'static str>,
+}
+
+pub const BUILTIN_TEXTURES: [BuiltinTexture; 4] = [
+    BuiltinTexture {
+        tile_type: TileType::Grass,
+        name: "Rocky Terrain",
+        base_color: "textures/terrain/rocky_terrain_02_diff_1k.png",
+        normal: Some("textures/terrain/rocky_terrain_02_nor_gl_1k_fixed.exr"),
+        roughness: Some("textures/terrain/roughness_l8.png"),
+        dispersion: Some("textures/terrain/rocky_terrain_02_disp_1k.png"),
+    },
+    BuiltinTexture {
+        tile_type: TileType::Dirt,
+        name: "Worn Soil",
+        base_color: "textures/terrain/rocky_terrain_02_diff_1k.png",
+        normal: Some("textures/terrain/rocky_terrain_02_nor_gl_1k_fixed.exr"),
+        roughness: Some("textures/terrain/roughness_l8.png"),
+        dispersion: Some("textures/terrain/rocky_terrain_02_disp_1k.png"),
+    },
+    BuiltinTexture {
+        tile_type: TileType::Sand,
+        name: "Sandstone",
+        base_color: "textures/terrain/rock/aerial_ground_rock_diff_1k.png",
+        normal: Some("textures/terrain/rock/aerial_ground_rock_nor_gl_1k_fixed.exr"),
+        roughness: Some("textures/terrain/rock/roughness_in_G.png"),
+        dispersion: Some("textures/terrain/rock/aerial_ground_rock_disp_1k.png"),
+    },
+    BuiltinTexture {
+        tile_type: TileType::Rock,
+        name: "Ground Rock",
+        base_color: "textures/terrain/rock/aerial_ground_rock_diff_1k.png",
+        normal: Some("textures/terrain/rock/aerial_ground_rock_nor_gl_1k_fixed.exr"),
+        roughness: Some("textures/terrain/rock/roughness_in_G.png"),
+        dispersion: Some("textures/terrain/rock/aerial_ground_rock_disp_1k.png"),
+    },
+];
+
+pub const BUILTIN_WALL_TEXTURE: BuiltinWallTexture = BuiltinWallTexture {
+    id: "wall",
+    name: "Cliff Wall",
+    base_color: "textures/terrain/rock/aerial_ground_rock_diff_1k.png",
+    normal: Some("textures/terrain/rock/aerial_ground_rock_nor_gl_1k_fixed.exr"),
+    roughness: Some("textures/terrain/rock/roughness_in_G.png"),
+};
+
 #[derive(Resource, Default)]
 pub struct TerrainTextureRegistry {
     entries: Vec<TerrainTextureEntry>,
@@ -75,6 +136,41 @@ impl TerrainTextureRegistry {
         self.wall_roughness_available = false;
     }
 
+    /// Registers the built-in textures by path only, without an asset server.
+    ///
+    /// The handles are placeholders, so this is only useful for export, which reads
+    /// the files from disk.
+    pub fn builtin_paths() -> Self {
+        let mut registry = Self::default();
+        for texture in &BUILTIN_TEXTURES {
+            registry.register_loaded(TerrainTextureEntry {
+                tile_type: texture.tile_type,
+                name: texture.name.to_string(),
+                preview: Handle::default(),
+                material: Handle::default(),
+                normal: None,
+                roughness: None,
+                dispersion: None,
+                diffuse_path: texture.base_color.to_string(),
+                normal_path: texture.normal.map(str::to_string),
+                roughness_path: texture.roughness.map(str::to_string),
+                dispersion_path: texture.dispersion.map(str::to_string),
+            });
+        }
+        let wall = &BUILTIN_WALL_TEXTURE;
+        registry.register_wall_texture(WallTextureEntry {
+            id: wall.id.to_string(),
+            name: wall.name.to_string(),
+            base_color: Handle::default(),
+            normal: None,
+            roughness: None,
+            diffuse_path: wall.base_color.to_string(),
+            normal_path: wall.normal.map(str::to_string),
+            roughness_path: wall.roughness.map(str::to_string),
+        });
+        registry
+    }
+
     pub fn load_and_register(
         &mut self,
         tile_type: TileType,
@@ -419,7 +515,7 @@ where
     None
 }
 
-fn create_fallback_image(template: &Image, color: [f32; 4]) -> Option<Image> {
+pub(crate) fn create_fallback_image(template: &Image, color: [f32; 4]) -> Option<Image> {
     let format = template.texture_descriptor.format;
     let pixel = color_to_bytes(format, color)?;
     let mut image = Image::new_fill(
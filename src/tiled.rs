@@ -0,0 +1,586 @@
+//! Conversion between `TileMap` and Tiled's JSON map format (`.tmj` / `.json`).
+//!
+//! Each distinct combination of tile type, ramp and walkability override becomes one tile
+//! of a generated tileset, described by custom tile properties. Elevation goes either to a
+//! second tile layer or to a property of the terrain tiles. [`TiledMapping`] names the
+//! layers and properties, so maps authored in Tiled only have to follow the same names.
+
+use std::collections::HashMap;
+use std::path::Path;
+
+use anyhow::{Context, Result, bail, ensure};
+use serde::{Deserialize, Serialize};
+use serde_json::{Value, json};
+
+use crate::types::{RampDirection, TileKind, TileMap, TileType, WalkOverride, WalkOverrideCell};
+
+/// Mapping config read by the editor when it is present in the working directory.
+pub const MAPPING_FILE: &str = "tiled_mapping.ron";
+/// Tiled stores flip and rotation flags in the top bits of each gid.
+const GID_FLAGS: u32 = 0xF000_0000;
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TiledMapping {
+    /// Tile layer holding the terrain tiles.
+    pub terrain_layer: String,
+    pub terrain_tileset: String,
+    /// Pixel size of a tile in Tiled.
+    pub tile_width: u32,
+    pub tile_height: u32,
+    pub tile_type_property: String,
+    /// Value of `tile_type_property` for each tile type.
+    pub tile_types: Vec<TileTypeName>,
+    /// String property holding `north`, `east`, `south` or `west`, or `auto` for a ramp
+    /// that picks its own direction. Tiles without it are floors.
+    pub ramp_property: String,
+    /// String property holding `blocked` or `open` for walkability overrides.
+    pub walk_property: String,
+    pub elevation: ElevationMapping,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct TileTypeName {
+    pub tile_type: TileType,
+    pub name: String,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub enum ElevationMapping {
+    /// A second tile layer whose tiles carry an integer elevation property.
+    Layer {
+        layer: String,
+        tileset: String,
+        property: String,
+    },
+    /// An integer property on the terrain tiles themselves.
+    TileProperty { property: String },
+}
+
+impl Default for TiledMapping {
+    fn default() -> Self {
+        Self {
+            terrain_layer: "terrain".to_string(),
+            terrain_tileset: "terrain".to_string(),
+            tile_width: 32,
+            tile_height: 32,
+            tile_type_property: "tile_type".to_string(),
+            tile_types: TileType::ALL
+                .into_iter()
+                .map(|tile_type| TileTypeName {
+                    tile_type,
+                    name: tile_type.identifier().to_string(),
+                })
+                .collect(),
+            ramp_property: "ramp".to_string(),
+            walk_property: "walk".to_string(),
+            elevation: ElevationMapping::Layer {
+                layer: "elevation".to_string(),
+                tileset: "elevation".to_string(),
+                property: "elevation".to_string(),
+            },
+        }
+    }
+}
+
+impl TiledMapping {
+    /// Reads the mapping from a RON file, falling back to the defaults if it does not exist.
+    pub fn load_or_default(path: &Path) -> Result<Self> {
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        let text = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read {}", path.display()))?;
+        ron::from_str(&text).with_context(|| format!("Invalid Tiled mapping {}", path.display()))
+    }
+
+    fn tile_type_name(&self, tile_type: TileType) -> String {
+        self.tile_types
+            .iter()
+            .find(|entry| entry.tile_type == tile_type)
+            .map(|entry| entry.name.clone())
+            .unwrap_or_else(|| tile_type.identifier().to_string())
+    }
+
+    fn tile_type_by_name(&self, name: &str) -> Option<TileType> {
+        self.tile_types
+            .iter()
+            .find(|entry| entry.name == name)
+            .map(|entry| entry.tile_type)
+    }
+}
+
+/// Everything about a tile that is stored on its terrain tileset entry.
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+struct TerrainKey {
+    tile_type: TileType,
+    kind: TileKind,
+    ramp_direction: Option<RampDirection>,
+    walk: Option<WalkOverride>,
+    /// Only set when elevation is stored as a tile property.
+    elevation: Option<i8>,
+}
+
+pub fn export_tiled(path: &Path, map: &TileMap, mapping: &TiledMapping) -> Result<()> {
+    let json = serde_json::to_vec_pretty(&map_to_tiled(map, mapping))?;
+    std::fs::write(path, json).with_context(|| format!("Failed to write {}", path.display()))
+}
+
+pub fn import_tiled(path: &Path, mapping: &TiledMapping) -> Result<TileMap> {
+    let bytes =
+        std::fs::read(path).with_context(|| format!("Failed to read {}", path.display()))?;
+    let tiled: TiledMap = serde_json::from_slice(&bytes)
+        .with_context(|| format!("{} is not a Tiled JSON map", path.display()))?;
+    tiled_to_map(&tiled, mapping)
+}
+
+fn map_to_tiled(map: &TileMap, mapping: &TiledMapping) -> Value {
+    let walk: HashMap<(u32, u32), WalkOverride> = map
+        .walk_overrides
+        .iter()
+        .map(|cell| ((cell.x, cell.y), cell.mode))
+        .collect();
+    let elevation_in_layer = matches!(mapping.elevation, ElevationMapping::Layer { .. });
+
+    // Tileset entries are created in order of first use so exports are deterministic.
+    let mut terrain_keys: Vec<TerrainKey> = Vec::new();
+    let mut terrain_ids: HashMap<TerrainKey, u32> = HashMap::new();
+    let mut elevations: Vec<i8> = Vec::new();
+    let mut terrain_data = Vec::with_capacity(map.tiles.len());
+    let mut elevation_local = Vec::with_capacity(map.tiles.len());
+
+    for y in 0..map.height {
+        for x in 0..map.width {
+            let tile = map.get(x, y);
+            let key = TerrainKey {
+                tile_type: tile.tile_type,
+                kind: tile.kind,
+                ramp_direction: match tile.kind {
+                    TileKind::Ramp => tile.ramp_direction,
+                    TileKind::Floor => None,
+                },
+                walk: walk.get(&(x, y)).copied(),
+                elevation: (!elevation_in_layer).then_some(tile.elevation),
+            };
+            let id = *terrain_ids.entry(key).or_insert_with(|| {
+                terrain_keys.push(key);
+                terrain_keys.len() as u32 - 1
+            });
+            terrain_data.push(id);
+
+            let elevation_id = match elevations.iter().position(|e| *e == tile.elevation) {
+                Some(index) => index,
+                None => {
+                    elevations.push(tile.elevation);
+                    elevations.len() - 1
+                }
+            };
+            elevation_local.push(elevation_id as u32);
+        }
+    }
+
+    let terrain_tiles: Vec<Value> = terrain_keys
+        .iter()
+        .enumerate()
+        .map(|(id, key)| {
+            let mut properties = vec![string_property(
+                &mapping.tile_type_property,
+                &mapping.tile_type_name(key.tile_type),
+            )];
+            if key.kind == TileKind::Ramp {
+                let direction = key.ramp_direction.map_or("auto", direction_name);
+                properties.push(string_property(&mapping.ramp_property, direction));
+            }
+            if let Some(mode) = key.walk {
+                properties.push(string_property(&mapping.walk_property, walk_name(mode)));
+            }
+            if let (Some(elevation), ElevationMapping::TileProperty { property }) =
+                (key.elevation, &mapping.elevation)
+            {
+                properties.push(int_property(property, elevation));
+            }
+            json!({ "id": id, "properties": properties })
+        })
+        .collect();
+
+    let mut tilesets = vec![tileset_json(
+        &mapping.terrain_tileset,
+        1,
+        terrain_tiles,
+        mapping,
+    )];
+    let mut layers = vec![layer_json(
+        1,
+        &mapping.terrain_layer,
+        map,
+        terrain_data.iter().map(|id| id + 1).collect(),
+    )];
+
+    if let ElevationMapping::Layer {
+        layer,
+        tileset,
+        property,
+    } = &mapping.elevation
+    {
+        let first_gid = terrain_keys.len() as u32 + 1;
+        let tiles = elevations
+            .iter()
+            .enumerate()
+            .map(|(id, elevation)| {
+                json!({ "id": id, "properties": [int_property(property, *elevation)] })
+            })
+            .collect();
+        tilesets.push(tileset_json(tileset, first_gid, tiles, mapping));
+        layers.push(layer_json(
+            2,
+            layer,
+            map,
+            elevation_local.iter().map(|id| id + first_gid).collect(),
+        ));
+    }
+
+    json!({
+        "type": "map",
+        "version": "1.10",
+        "orientation": "orthogonal",
+        "renderorder": "right-down",
+        "infinite": false,
+        "width": map.width,
+        "height": map.height,
+        "tilewidth": mapping.tile_width,
+        "tileheight": mapping.tile_height,
+        "nextlayerid": layers.len() + 1,
+        "nextobjectid": 1,
+        "layers": layers,
+        "tilesets": tilesets,
+    })
+}
+
+fn tileset_json(name: &str, first_gid: u32, tiles: Vec<Value>, mapping: &TiledMapping) -> Value {
+    json!({
+        "firstgid": first_gid,
+        "name": name,
+        "tilewidth": mapping.tile_width,
+        "tileheight": mapping.tile_height,
+        "tilecount": tiles.len(),
+        "columns": 0,
+        "margin": 0,
+        "spacing": 0,
+        "tiles": tiles,
+    })
+}
+
+fn layer_json(id: u32, name: &str, map: &TileMap, data: Vec<u32>) -> Value {
+    json!({
+        "id": id,
+        "name": name,
+        "type": "tilelayer",
+        "x": 0,
+        "y": 0,
+        "width": map.width,
+        "height": map.height,
+        "opacity": 1,
+        "visible": true,
+        "data": data,
+    })
+}
+
+fn string_property(name: &str, value: &str) -> Value {
+    json!({ "name": name, "type": "string", "value": value })
+}
+
+fn int_property(name: &str, value: i8) -> Value {
+    json!({ "name": name, "type": "int", "value": value })
+}
+
+#[derive(Deserialize)]
+struct TiledMap {
+    width: u32,
+    height: u32,
+    #[serde(default)]
+    infinite: bool,
+    #[serde(default)]
+    orientation: String,
+    #[serde(default)]
+    layers: Vec<TiledLayer>,
+    #[serde(default)]
+    tilesets: Vec<TiledTileset>,
+}
+
+#[derive(Deserialize)]
+struct TiledLayer {
+    name: String,
+    #[serde(rename = "type")]
+    kind: String,
+    #[serde(default)]
+    data: Option<Value>,
+    #[serde(default)]
+    encoding: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct TiledTileset {
+    firstgid: u32,
+    #[serde(default)]
+    source: Option<String>,
+    #[serde(default)]
+    tiles: Vec<TiledTile>,
+}
+
+#[derive(Deserialize)]
+struct TiledTile {
+    id: u32,
+    #[serde(default)]
+    properties: Vec<TiledProperty>,
+}
+
+#[derive(Deserialize)]
+struct TiledProperty {
+    name: String,
+    value: Value,
+}
+
+fn tiled_to_map(tiled: &TiledMap, mapping: &TiledMapping) -> Result<TileMap> {
+    ensure!(!tiled.infinite, "Infinite Tiled maps are not supported");
+    ensure!(
+        tiled.orientation.is_empty() || tiled.orientation == "orthogonal",
+        "Only orthogonal Tiled maps are supported, not {}",
+        tiled.orientation
+    );
+    ensure!(
+        tiled.width > 0 && tiled.height > 0,
+        "The Tiled map is empty"
+    );
+    if let Some(source) = tiled.tilesets.iter().find_map(|set| set.source.as_ref()) {
+        bail!("External tileset {source} is not supported; embed it in the map");
+    }
+
+    let properties = TileProperties::new(&tiled.tilesets);
+    let cells = tiled
+        .width
+        .checked_mul(tiled.height)
+        .map(|cells| cells as usize)
+        .ok_or_else(|| {
+            anyhow::anyhow!(
+                "Map dimensions {}x{} are too large",
+                tiled.width,
+                tiled.height
+            )
+        })?;
+    let terrain = layer_data(tiled, &mapping.terrain_layer, cells)?
+        .with_context(|| format!("The map has no tile layer named {}", mapping.terrain_layer))?;
+
+    let mut map = TileMap::new(tiled.width, tiled.height);
+    for (index, gid) in terrain.iter().enumerate() {
+        let (x, y) = (index as u32 % map.width, index as u32 / map.width);
+        let tile = &mut map.tiles[index];
+        if let Some(name) = properties.string(*gid, &mapping.tile_type_property) {
+            tile.tile_type = mapping.tile_type_by_name(name).with_context(|| {
+                format!("Tile ({x}, {y}) has unknown tile type {name}; add it to the mapping")
+            })?;
+        }
+        if let Some(ramp) = properties.string(*gid, &mapping.ramp_property) {
+            tile.kind = TileKind::Ramp;
+            tile.ramp_direction = match ramp {
+                "auto" => None,
+                name => Some(
+                    parse_direction(name)
+                        .with_context(|| format!("Tile ({x}, {y}) has unknown ramp {name}"))?,
+                ),
+            };
+        }
+        if let Some(walk) = properties.string(*gid, &mapping.walk_property) {
+            let mode = parse_walk(walk)
+                .with_context(|| format!("Tile ({x}, {y}) has unknown walk override {walk}"))?;
+            map.walk_overrides.push(WalkOverrideCell { x, y, mode });
+        }
+        if let ElevationMapping::TileProperty { property } = &mapping.elevation {
+            tile.elevation = properties.elevation(*gid, property, x, y)?.unwrap_or(0);
+        }
+    }
+
+    if let ElevationMapping::Layer {
+        layer, property, ..
+    } = &mapping.elevation
+        && let Some(elevations) = layer_data(tiled, layer, cells)?
+    {
+        for (index, gid) in elevations.iter().enumerate() {
+            let (x, y) = (index as u32 % map.width, index as u32 / map.width);
+            if let Some(elevation) = properties.elevation(*gid, property, x, y)? {
+                map.tiles[index].elevation = elevation;
+            }
+        }
+    }
+
+    Ok(map)
+}
+
+/// Gids of a top-level tile layer with flip flags removed, or `None` if there is no such layer.
+fn layer_data(tiled: &TiledMap, name: &str, cells: usize) -> Result<Option<Vec<u32>>> {
+    let Some(layer) = tiled
+        .layers
+        .iter()
+        .find(|layer| layer.kind == "tilelayer" && layer.name == name)
+    else {
+        return Ok(None);
+    };
+    if let Some(encoding) = layer.encoding.as_deref()
+        && encoding != "csv"
+    {
+        bail!("Layer {name} uses {encoding} encoding; save the map with CSV layer data");
+    }
+    let data: Vec<u32> = layer
+        .data
+        .clone()
+        .map(serde_json::from_value)
+        .transpose()
+        .with_context(|| format!("Layer {name} has invalid tile data"))?
+        .unwrap_or_default();
+    ensure!(
+        data.len() == cells,
+        "Layer {name} has {} tiles, expected {cells}",
+        data.len()
+    );
+    Ok(Some(data.into_iter().map(|gid| gid & !GID_FLAGS).collect()))
+}
+
+/// Custom properties of every tile in the map's tilesets, keyed by gid.
+struct TileProperties<'a> {
+    by_gid: HashMap<u32, &'a [TiledProperty]>,
+}
+
+impl<'a> TileProperties<'a> {
+    fn new(tilesets: &'a [TiledTileset]) -> Self {
+        let by_gid = tilesets
+            .iter()
+            .flat_map(|set| {
+                set.tiles
+                    .iter()
+                    .map(move |tile| (set.firstgid + tile.id, tile.properties.as_slice()))
+            })
+            .collect();
+        Self { by_gid }
+    }
+
+    fn get(&self, gid: u32, name: &str) -> Option<&'a Value> {
+        self.by_gid
+            .get(&gid)?
+            .iter()
+            .find(|property| property.name == name)
+            .map(|property| &property.value)
+    }
+
+    fn string(&self, gid: u32, name: &str) -> Option<&'a str> {
+        self.get(gid, name)?.as_str()
+    }
+
+    fn elevation(&self, gid: u32, name: &str, x: u32, y: u32) -> Result<Option<i8>> {
+        let Some(value) = self.get(gid, name) else {
+            return Ok(None);
+        };
+        let elevation = value
+            .as_i64()
+            .and_then(|value| i8::try_from(value).ok())
+            .with_context(|| format!("Tile ({x}, {y}) has invalid elevation {value}"))?;
+        Ok(Some(elevation))
+    }
+}
+
+fn direction_name(direction: RampDirection) -> &'static str {
+    match direction {
+        RampDirection::North => "north",
+        RampDirection::East => "east",
+        RampDirection::South => "south",
+        RampDirection::West => "west",
+    }
+}
+
+fn parse_direction(name: &str) -> Option<RampDirection> {
+    RampDirection::ALL
+        .into_iter()
+        .find(|direction| direction_name(*direction) == name)
+}
+
+fn walk_name(mode: WalkOverride) -> &'static str {
+    match mode {
+        WalkOverride::Blocked => "blocked",
+        WalkOverride::Open => "open",
+    }
+}
+
+fn parse_walk(name: &str) -> Option<WalkOverride> {
+    match name {
+        "blocked" => Some(WalkOverride::Blocked),
+        "open" => Some(WalkOverride::Open),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The mapping file shipped in the repository.
+    fn default_mapping() -> TiledMapping {
+        ron::from_str(include_str!("../tiled_mapping.ron")).unwrap()
+    }
+
+    fn sample_map() -> TileMap {
+        let mut map = TileMap::new(4, 3);
+        let index = map.idx(1, 0);
+        map.tiles[index].tile_type = TileType::Sand;
+        let index = map.idx(2, 1);
+        map.tiles[index].elevation = 2;
+        map.tiles[index].tile_type = TileType::Rock;
+        let index = map.idx(1, 1);
+        map.tiles[index].kind = TileKind::Ramp;
+        map.tiles[index].elevation = 1;
+        map.tiles[index].ramp_direction = Some(RampDirection::West);
+        let index = map.idx(3, 2);
+        map.tiles[index].kind = TileKind::Ramp;
+        map.tiles[index].elevation = 1;
+        map.set_walk_override(0, 2, Some(WalkOverride::Blocked));
+        map.set_walk_override(3, 2, Some(WalkOverride::Open));
+        map
+    }
+
+    #[test]
+    fn export_then_import_preserves_map() {
+        let mapping = default_mapping();
+        let map = sample_map();
+        let path =
+            std::env::temp_dir().join(format!("tiled_round_trip_{}.tmj", std::process::id()));
+        export_tiled(&path, &map, &mapping).unwrap();
+        let imported = import_tiled(&path, &mapping);
+        std::fs::remove_file(&path).unwrap();
+        let imported = imported.unwrap();
+
+        assert_eq!((imported.width, imported.height), (map.width, map.height));
+        for (a, b) in map.tiles.iter().zip(&imported.tiles) {
+            assert_eq!(a.kind, b.kind);
+            assert_eq!(a.tile_type, b.tile_type);
+            assert_eq!(a.elevation, b.elevation);
+            assert_eq!(a.ramp_direction, b.ramp_direction);
+            assert_eq!((a.x, a.y), (b.x, b.y));
+        }
+        let overrides = |map: &TileMap| {
+            map.walk_overrides
+                .iter()
+                .map(|cell| (cell.x, cell.y, cell.mode))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(overrides(&imported), overrides(&map));
+    }
+
+    #[test]
+    fn non_csv_layers_are_rejected() {
+        let mapping = default_mapping();
+        let mut json = map_to_tiled(&sample_map(), &mapping);
+        json["layers"][0]["encoding"] = json!("base64");
+        json["layers"][0]["data"] = json!("AQAAAAEAAAA=");
+        let tiled: TiledMap = serde_json::from_value(json).unwrap();
+
+        let error = tiled_to_map(&tiled, &mapping).unwrap_err().to_string();
+        assert!(error.contains("base64 encoding"), "{error}");
+    }
+}
@@ -1,13 +1,13 @@
 use bincode::{Decode, Encode};
 use serde::{Deserialize, Serialize};
 
-#[derive(Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Debug, Encode, Decode)]
+#[derive(Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Hash, Debug, Encode, Decode)]
 pub enum TileKind {
     Floor,
     Ramp,
 }
 
-#[derive(Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Debug, Encode, Decode)]
+#[derive(Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Hash, Debug, Encode, Decode)]
 pub enum RampDirection {
     North,
     East,
@@ -32,6 +32,10 @@ impl RampDirection {
         }
     }
 
+    pub fn opposite(self) -> RampDirection {
+        self.next().next()
+    }
+
     pub fn offset(self) -> (i32, i32) {
         match self {
             RampDirection::North => (0, -1),
@@ -95,11 +99,29 @@ pub struct Tile {
     pub ramp_direction: Option<RampDirection>,
 }
 
+/// Designer override for a single cell of the generated walkability grid.
+#[derive(Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Hash, Debug, Encode, Decode)]
+pub enum WalkOverride {
+    /// The cell is never walkable, regardless of terrain.
+    Blocked,
+    /// The cell connects to every non-blocked cardinal neighbour, ignoring cliffs.
+    Open,
+}
+
+#[derive(Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Debug, Encode, Decode)]
+pub struct WalkOverrideCell {
+    pub x: u32,
+    pub y: u32,
+    pub mode: WalkOverride,
+}
+
 #[derive(Serialize, Deserialize, Debug, Encode, Decode, Clone)]
 pub struct TileMap {
     pub width: u32,
     pub height: u32,
     pub tiles: Vec<Tile>, // row-major
+    #[serde(default)]
+    pub walk_overrides: Vec<WalkOverrideCell>,
 }
 
 impl TileMap {
@@ -108,15 +130,16 @@ impl TileMap {
             width: w,
             height: h,
             tiles: (0..w * h)
-                .map(|_| Tile {
+                .map(|i| Tile {
                     kind: TileKind::Floor,
                     tile_type: TileType::default(),
                     elevation: 0,
-                    x: 0,
-                    y: 0,
+                    x: i % w,
+                    y: i / w,
                     ramp_direction: None,
                 })
                 .collect(),
+            walk_overrides: Vec::new(),
         }
     }
     pub fn idx(&self, x: u32, y: u32) -> usize {
@@ -129,6 +152,105 @@ impl TileMap {
         let i = self.idx(x, y);
         self.tiles[i] = t;
     }
+    /// The tiles in `rect` as a map of their own, e.g. to hand part of a large map
+    /// to another thread. Tiles keep their original coordinates; walkability
+    /// overrides are not copied.
+    pub fn crop(&self, rect: TileRect) -> TileMap {
+        let rect = rect.intersect(TileRect::full(self));
+        TileMap {
+            width: rect.x1.saturating_sub(rect.x0),
+            height: rect.y1.saturating_sub(rect.y0),
+            tiles: rect.tiles().map(|(x, y)| self.get(x, y).clone()).collect(),
+            walk_overrides: Vec::new(),
+        }
+    }
+    /// Sets or clears the walkability override for a cell. Returns `true` if anything changed.
+    pub fn set_walk_override(&mut self, x: u32, y: u32, mode: Option<WalkOverride>) -> bool {
+        let existing = self
+            .walk_overrides
+            .iter()
+            .position(|cell| cell.x == x && cell.y == y);
+        match (existing, mode) {
+            (Some(index), Some(mode)) => {
+                if self.walk_overrides[index].mode == mode {
+                    return false;
+                }
+                self.walk_overrides[index].mode = mode;
+            }
+            (Some(index), None) => {
+                self.walk_overrides.remove(index);
+            }
+            (None, Some(mode)) => self.walk_overrides.push(WalkOverrideCell { x, y, mode }),
+            (None, None) => return false,
+        }
+        true
+    }
+}
+
+/// Rectangle of tile coordinates; `x0`/`y0` are inclusive, `x1`/`y1` exclusive.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct TileRect {
+    pub x0: u32,
+    pub y0: u32,
+    pub x1: u32,
+    pub y1: u32,
+}
+
+impl TileRect {
+    pub fn tile(x: u32, y: u32) -> Self {
+        Self {
+            x0: x,
+            y0: y,
+            x1: x + 1,
+            y1: y + 1,
+        }
+    }
+
+    pub fn full(map: &TileMap) -> Self {
+        Self {
+            x0: 0,
+            y0: 0,
+            x1: map.width,
+            y1: map.height,
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.x0 >= self.x1 || self.y0 >= self.y1
+    }
+
+    pub fn union(self, other: TileRect) -> Self {
+        Self {
+            x0: self.x0.min(other.x0),
+            y0: self.y0.min(other.y0),
+            x1: self.x1.max(other.x1),
+            y1: self.y1.max(other.y1),
+        }
+    }
+
+    pub fn intersect(self, other: TileRect) -> Self {
+        Self {
+            x0: self.x0.max(other.x0),
+            y0: self.y0.max(other.y0),
+            x1: self.x1.min(other.x1),
+            y1: self.y1.min(other.y1),
+        }
+    }
+
+    /// Grows the rectangle by `tiles` on every side, clamped to the map.
+    pub fn grow(self, tiles: u32, map: &TileMap) -> Self {
+        Self {
+            x0: self.x0.saturating_sub(tiles),
+            y0: self.y0.saturating_sub(tiles),
+            x1: self.x1.saturating_add(tiles),
+            y1: self.y1.saturating_add(tiles),
+        }
+        .intersect(Self::full(map))
+    }
+
+    pub fn tiles(self) -> impl Iterator<Item = (u32, u32)> {
+        (self.y0..self.y1).flat_map(move |y| (self.x0..self.x1).map(move |x| (x, y)))
+    }
 }
 
 pub const TILE_SIZE: f32 = 2.0; // world units per tile
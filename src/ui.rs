@@ -1,12 +1,22 @@
-use crate::editor::{EditorTool, ExportStatus};
+use crate::autosave::{self, AutosaveState};
+use crate::editor::{
+    EditorState, EditorTool, ExportStatus, HeightmapImage, HeightmapImportDialog, PendingAction,
+};
 use crate::export;
+use crate::generate::{self, MapTemplate, NewMapSettings};
+use crate::heightmap;
 use crate::io::{load_map, save_map};
+use crate::raster::{self, HeightFormat};
 use crate::runtime::RuntimeSplatMap;
-use crate::terrain::TerrainMeshSet;
+use crate::terrain::{TerrainMeshSet, TopNormals};
+use crate::texture::material::TerrainMaterial;
+use crate::tiled;
 use crate::types::*;
+use crate::validation::{self, Severity};
 use bevy::prelude::*;
 use bevy::render::texture::Image;
 use bevy::tasks::{IoTaskPool, block_on};
+use bevy::window::{PrimaryWindow, WindowCloseRequested};
 use bevy_egui::{EguiContexts, egui};
 use rfd::AsyncFileDialog;
 use std::path::{Path, PathBuf};
@@ -16,14 +26,29 @@ use crate::texture::registry::TerrainTextureRegistry;
 pub struct UiPlugin;
 impl Plugin for UiPlugin {
     fn build(&self, app: &mut App) {
-        app.add_systems(Update, ui_panel.before(TerrainMeshSet::Rebuild));
+        app.add_systems(
+            Update,
+            (
+                ui_panel,
+                validation_panel,
+                new_map_dialog,
+                heightmap_dialog,
+                recovery_prompt,
+                unsaved_changes_prompt,
+            )
+                .chain()
+                .before(TerrainMeshSet::Rebuild),
+        )
+        .add_systems(Update, (handle_close_requests, update_window_title));
     }
 }
 
 fn ui_panel(
     mut egui_ctx: EguiContexts,
     mut state: ResMut<crate::editor::EditorState>,
-    textures: Res<TerrainTextureRegistry>,
+    mut textures: ResMut<TerrainTextureRegistry>,
+    mut materials: ResMut<Assets<TerrainMaterial>>,
+    asset_server: Res<AssetServer>,
     runtime_splat: Option<Res<RuntimeSplatMap>>,
     images: Res<Assets<Image>>,
 ) {
@@ -54,6 +79,16 @@ fn ui_panel(
                 EditorTool::RotateRamp,
                 "Rotate Ramp",
             );
+            ui.selectable_value(
+                &mut state.current_tool,
+                EditorTool::Walkability,
+                "Walkability",
+            );
+            ui.selectable_value(
+                &mut state.current_tool,
+                EditorTool::PathPreview,
+                "Path Preview",
+            );
 
             if state.current_tool == EditorTool::Paint {
                 ui.separator();
@@ -62,6 +97,22 @@ fn ui_panel(
                 ui.selectable_value(&mut state.current_kind, TileKind::Ramp, "Ramp");
             }
 
+            if state.current_tool == EditorTool::Walkability {
+                ui.separator();
+                ui.label("Override:");
+                ui.selectable_value(
+                    &mut state.current_walk_override,
+                    Some(WalkOverride::Blocked),
+                    "Block",
+                );
+                ui.selectable_value(
+                    &mut state.current_walk_override,
+                    Some(WalkOverride::Open),
+                    "Open",
+                );
+                ui.selectable_value(&mut state.current_walk_override, None, "Clear");
+            }
+
             ui.separator();
             ui.label("Elevation:");
             for e in 0..=3 {
@@ -69,28 +120,17 @@ fn ui_panel(
             }
 
             ui.separator();
+            if ui.button("New…").clicked() && state.new_map_dialog.is_none() {
+                state.new_map_dialog = Some(NewMapSettings {
+                    width: state.map.width,
+                    height: state.map.height,
+                    ..default()
+                });
+            }
             if ui.button("Save…").clicked() && state.save_dialog_task.is_none() {
-                let mut dialog = AsyncFileDialog::new().set_title("Save Map");
-                if let Some(path) = state.current_file_path.as_ref() {
-                    if let Some(parent) = path.parent() {
-                        dialog = dialog.set_directory(parent);
-                    }
-                    if let Some(file_name) = path.file_name().and_then(|name| name.to_str()) {
-                        dialog = dialog.set_file_name(file_name);
-                    }
-                }
-
-                state.save_dialog_task = Some(IoTaskPool::get().spawn(async move {
-                    dialog
-                        .save_file()
-                        .await
-                        .map(|file| file.path().to_path_buf())
-                }));
+                open_save_dialog(&mut state);
             }
-            if ui.button("Export…").clicked()
-                && state.export_dialog_task.is_none()
-                && state.export_task.is_none()
-            {
+            if ui.button("Export…").clicked() && export_idle(&state) {
                 let mut dialog = AsyncFileDialog::new().set_title("Export Map");
                 dialog = dialog.add_filter("Tile Map Package", &["tmemapdata"]);
                 if let Some(path) = state.current_file_path.as_ref() {
@@ -111,24 +151,40 @@ fn ui_panel(
                         .map(|file| file.path().to_path_buf())
                 }));
             }
+            if ui.button("Export rasters…").clicked() && export_idle(&state) {
+                open_raster_dialog(&mut state);
+            }
             if ui.button("Load…").clicked() && state.load_dialog_task.is_none() {
-                let mut dialog = AsyncFileDialog::new().set_title("Open Map");
-                if let Some(path) = state.current_file_path.as_ref() {
-                    if let Some(parent) = path.parent() {
-                        dialog = dialog.set_directory(parent);
+                request_action(&mut state, PendingAction::Load);
+            }
+            if ui.button("Open package…").clicked()
+                && state.package_dialog_task.is_none()
+                && state.package_import_task.is_none()
+            {
+                request_action(&mut state, PendingAction::OpenPackage);
+            }
+            ui.menu_button("Tiled", |ui| {
+                if ui.button("Import map…").clicked() {
+                    ui.close_menu();
+                    if state.tiled_import_task.is_none() {
+                        request_action(&mut state, PendingAction::ImportTiled);
                     }
                 }
-
-                state.load_dialog_task = Some(IoTaskPool::get().spawn(async move {
-                    dialog
-                        .pick_file()
-                        .await
-                        .map(|file| file.path().to_path_buf())
-                }));
+                if ui.button("Export map…").clicked() {
+                    ui.close_menu();
+                    if state.tiled_export_task.is_none() {
+                        open_tiled_export_dialog(&mut state);
+                    }
+                }
+            });
+            if ui.button("Import heightmap…").clicked() && state.heightmap_dialog.is_none() {
+                state.heightmap_dialog = Some(HeightmapImportDialog::default());
             }
 
             ui.separator();
             ui.checkbox(&mut state.show_grid, "Gridlines");
+            ui.checkbox(&mut state.show_walkability, "Walkability");
+            ui.toggle_value(&mut state.show_validation, "Validate");
         });
 
         if !palette_items.is_empty() {
@@ -187,9 +243,82 @@ fn ui_panel(
                 });
             });
         }
+        ui.separator();
+        ui.collapsing("Raster export", |ui| {
+            ui.horizontal(|ui| {
+                ui.label("Height samples per tile:");
+                ui.add(egui::DragValue::new(&mut state.raster_options.samples_per_tile).range(1..=16));
+                ui.separator();
+                ui.label("Heightmap:");
+                ui.selectable_value(
+                    &mut state.raster_options.height_format,
+                    HeightFormat::Png16,
+                    "16-bit PNG",
+                );
+                ui.selectable_value(
+                    &mut state.raster_options.height_format,
+                    HeightFormat::Raw16,
+                    "16-bit RAW",
+                );
+                ui.separator();
+                ui.checkbox(&mut state.export_rasters_in_package, "Include in packages");
+            });
+        });
+        ui.collapsing("Package export", |ui| {
+            ui.checkbox(
+                &mut state.export_chunked_mesh,
+                "One mesh node per chunk (for culling in large maps)",
+            );
+            ui.horizontal(|ui| {
+                ui.label("Top normals:");
+                ui.selectable_value(&mut state.export_top_normals, TopNormals::Flat, "Flat");
+                ui.selectable_value(&mut state.export_top_normals, TopNormals::Smooth, "Smooth");
+            });
+            ui.checkbox(
+                &mut state.export_optimize_mesh,
+                "Merge coplanar tops and cliff walls",
+            );
+            ui.horizontal(|ui| {
+                ui.label("LOD levels:");
+                ui.add(
+                    egui::DragValue::new(&mut state.export_mesh_lods)
+                        .range(0..=export::MAX_MESH_LODS),
+                );
+            });
+        });
         if let Some(path) = state.current_file_path.as_ref() {
             ui.separator();
-            ui.label(format!("Current map: {}", path.display()));
+            let marker = if state.map_modified { "*" } else { "" };
+            ui.label(format!("Current map: {}{marker}", path.display()));
+        }
+
+        if state.current_tool == EditorTool::PathPreview {
+            ui.separator();
+            match (state.path_start, state.path_goal, state.path_preview.as_ref()) {
+                (None, _, _) => {
+                    ui.label("Click a tile to set the path start.");
+                }
+                (Some((sx, sy)), None, _) => {
+                    ui.label(format!("Start ({sx}, {sy}) – click a tile to set the goal."));
+                }
+                (Some((sx, sy)), Some((gx, gy)), Some(preview)) => match preview.result.as_ref() {
+                    Some(path) => {
+                        ui.label(format!(
+                            "Path ({sx}, {sy}) → ({gx}, {gy}): {} steps, {:.1} tiles, {:.1} world units",
+                            path.cells.len().saturating_sub(1),
+                            path.length_tiles,
+                            preview.length_world,
+                        ));
+                    }
+                    None => {
+                        ui.colored_label(
+                            egui::Color32::from_rgb(198, 40, 40),
+                            format!("No path from ({sx}, {sy}) to ({gx}, {gy})"),
+                        );
+                    }
+                },
+                (Some(_), Some(_), None) => {}
+            }
         }
 
         if let Some(status) = state.last_export_status.as_ref() {
@@ -207,12 +336,23 @@ fn ui_panel(
 
     if let Some(task) = state.save_dialog_task.as_mut() {
         if task.is_finished() {
-            if let Some(path) = block_on(state.save_dialog_task.take().unwrap()) {
-                if let Err(err) = save_map(&path, &state.map) {
-                    eprintln!("Failed to save map: {err:?}");
-                } else {
-                    state.current_file_path = Some(path);
+            match block_on(state.save_dialog_task.take().unwrap()) {
+                Some(path) => {
+                    if let Err(err) = save_map(&path, &state.map) {
+                        eprintln!("Failed to save map: {err:?}");
+                        state.last_export_status =
+                            Some(ExportStatus::Failure(format!("Save failed: {err:#}")));
+                        state.after_save = None;
+                    } else {
+                        state.current_file_path = Some(path);
+                        state.map_modified = false;
+                        if let Some(action) = state.after_save.take() {
+                            run_action(&mut state, action);
+                        }
+                    }
                 }
+                // Cancelling the save dialog also cancels whatever was waiting on it.
+                None => state.after_save = None,
             }
         }
     }
@@ -225,6 +365,15 @@ fn ui_panel(
                     Ok((descriptors, wall_descriptor)) => {
                         let map_clone = state.map.clone();
                         let export_name = infer_export_name(&state, &export_path);
+                        let options = export::PackageExportOptions {
+                            rasters: state
+                                .export_rasters_in_package
+                                .then_some(state.raster_options),
+                            chunked_mesh: state.export_chunked_mesh,
+                            top_normals: state.export_top_normals,
+                            optimize_mesh: state.export_optimize_mesh,
+                            mesh_lods: state.export_mesh_lods,
+                        };
                         let export_path_clone = export_path.clone();
                         let splat_png_result = if let Some(runtime) = runtime_splat.as_ref() {
                             if let Some(image) = images.get(&runtime.handle) {
@@ -247,6 +396,7 @@ fn ui_panel(
                                         descriptors,
                                         wall_descriptor,
                                         splat_png,
+                                        options,
                                     )
                                     .map(|_| export_path_clone)
                                 }));
@@ -268,6 +418,18 @@ fn ui_panel(
         }
     }
 
+    if let Some(task) = state.raster_dialog_task.as_mut()
+        && task.is_finished()
+        && let Some(path) = block_on(state.raster_dialog_task.take().unwrap())
+    {
+        let map = state.map.clone();
+        let options = state.raster_options;
+        state.last_export_status = None;
+        state.export_task = Some(
+            IoTaskPool::get().spawn(async move { raster::export_rasters(&path, &map, &options) }),
+        );
+    }
+
     if let Some(task) = state.export_task.as_mut() {
         if task.is_finished() {
             match block_on(state.export_task.take().unwrap()) {
@@ -291,19 +453,812 @@ fn ui_panel(
             if let Some(path) = block_on(state.load_dialog_task.take().unwrap()) {
                 match load_map(&path) {
                     Ok(m) => {
-                        state.map = m;
-                        state.map_dirty = true;
+                        state.replace_map(m);
+                        state.map_modified = false;
                         state.current_file_path = Some(path);
                     }
                     Err(err) => {
                         eprintln!("Failed to load map: {err:?}");
+                        state.last_export_status =
+                            Some(ExportStatus::Failure(format!("Load failed: {err:#}")));
+                    }
+                }
+            }
+        }
+    }
+
+    if let Some(task) = state.tiled_import_task.as_mut()
+        && task.is_finished()
+        && let Some(path) = block_on(state.tiled_import_task.take().unwrap())
+    {
+        match tiled::TiledMapping::load_or_default(Path::new(tiled::MAPPING_FILE))
+            .and_then(|mapping| tiled::import_tiled(&path, &mapping))
+        {
+            Ok(map) => {
+                state.start_new_map(map);
+                // Like packages, Tiled maps are not map files, so saving asks for a path.
+                state.map_modified = true;
+                state.last_export_status = Some(ExportStatus::Success(format!(
+                    "Imported Tiled map {}",
+                    path.display()
+                )));
+            }
+            Err(err) => {
+                eprintln!("Failed to import Tiled map: {err:?}");
+                state.last_export_status = Some(ExportStatus::Failure(format!(
+                    "Tiled import failed: {err:#}"
+                )));
+            }
+        }
+    }
+
+    if let Some(task) = state.tiled_export_task.as_mut()
+        && task.is_finished()
+        && let Some(path) = block_on(state.tiled_export_task.take().unwrap())
+    {
+        let path = ensure_extension(path, "tmj");
+        match tiled::TiledMapping::load_or_default(Path::new(tiled::MAPPING_FILE))
+            .and_then(|mapping| tiled::export_tiled(&path, &state.map, &mapping))
+        {
+            Ok(()) => {
+                state.last_export_status = Some(ExportStatus::Success(format!(
+                    "Exported Tiled map to {}",
+                    path.display()
+                )));
+            }
+            Err(err) => {
+                eprintln!("Failed to export Tiled map: {err:?}");
+                state.last_export_status = Some(ExportStatus::Failure(format!(
+                    "Tiled export failed: {err:#}"
+                )));
+            }
+        }
+    }
+
+    if let Some(task) = state.package_dialog_task.as_mut()
+        && task.is_finished()
+        && let Some(path) = block_on(state.package_dialog_task.take().unwrap())
+    {
+        state.package_import_task = Some(IoTaskPool::get().spawn(async move {
+            export::import_package(&path, Path::new("assets")).map(|package| (path, package))
+        }));
+    }
+
+    if let Some(task) = state.package_import_task.as_mut()
+        && task.is_finished()
+    {
+        match block_on(state.package_import_task.take().unwrap()) {
+            Ok((path, package)) => {
+                register_package_textures(&package, &mut textures, &asset_server, &mut materials);
+                state.replace_map(package.map);
+                // The package is not a map file, so saving should ask for a new path.
+                state.map_modified = true;
+                state.current_file_path = None;
+                state.last_export_status = Some(ExportStatus::Success(format!(
+                    "Opened package {} ({})",
+                    package.name,
+                    path.display()
+                )));
+            }
+            Err(err) => {
+                eprintln!("Failed to open package: {err:?}");
+                state.last_export_status = Some(ExportStatus::Failure(format!(
+                    "Open package failed: {err:#}"
+                )));
+            }
+        }
+    }
+}
+
+/// Both export dialogs hand their result to `export_task`, so only one export
+/// may be in flight from picking a path to writing the files.
+fn export_idle(state: &EditorState) -> bool {
+    state.export_dialog_task.is_none()
+        && state.raster_dialog_task.is_none()
+        && state.export_task.is_none()
+}
+
+/// Asks where to write the rasters; the chosen file name becomes their common prefix.
+fn open_raster_dialog(state: &mut EditorState) {
+    let mut dialog = AsyncFileDialog::new().set_title("Export Rasters");
+    let stem = state
+        .current_file_path
+        .as_ref()
+        .and_then(|path| path.file_stem())
+        .and_then(|stem| stem.to_str())
+        .unwrap_or("map");
+    dialog = dialog.set_file_name(stem);
+    if let Some(parent) = state
+        .current_file_path
+        .as_ref()
+        .and_then(|path| path.parent())
+    {
+        dialog = dialog.set_directory(parent);
+    }
+
+    state.raster_dialog_task = Some(IoTaskPool::get().spawn(async move {
+        dialog
+            .save_file()
+            .await
+            .map(|file| file.path().to_path_buf())
+    }));
+}
+
+fn register_package_textures(
+    package: &export::ImportedPackage,
+    textures: &mut TerrainTextureRegistry,
+    asset_server: &AssetServer,
+    materials: &mut Assets<TerrainMaterial>,
+) {
+    for texture in &package.textures {
+        textures.load_and_register(
+            texture.tile_type,
+            texture.identifier.clone(),
+            asset_server,
+            materials,
+            &texture.diffuse,
+            texture.normal.as_deref(),
+            texture.roughness.as_deref(),
+            None,
+        );
+    }
+
+    if let Some(wall) = package.wall_texture.as_ref() {
+        textures.load_and_register_wall(
+            wall.identifier.clone(),
+            wall.identifier.clone(),
+            asset_server,
+            &wall.diffuse,
+            wall.normal.as_deref(),
+            wall.roughness.as_deref(),
+        );
+    }
+}
+
+fn validation_panel(mut egui_ctx: EguiContexts, mut state: ResMut<crate::editor::EditorState>) {
+    if !state.show_validation {
+        return;
+    }
+
+    let mut open = true;
+    let mut focus = None;
+    let mut fix = None;
+    let mut fix_all = false;
+
+    egui::Window::new("Map Validation")
+        .open(&mut open)
+        .default_width(380.0)
+        .show(egui_ctx.ctx_mut(), |ui| {
+            let Some(diagnostics) = state.diagnostics.as_ref() else {
+                ui.label("Validating…");
+                return;
+            };
+
+            if diagnostics.is_empty() {
+                ui.colored_label(egui::Color32::from_rgb(56, 142, 60), "No problems found.");
+                return;
+            }
+
+            ui.horizontal(|ui| {
+                let errors = diagnostics
+                    .iter()
+                    .filter(|diagnostic| diagnostic.severity == Severity::Error)
+                    .count();
+                ui.label(format!(
+                    "{errors} error(s), {} warning(s)",
+                    diagnostics.len() - errors
+                ));
+                if diagnostics.iter().any(|diagnostic| diagnostic.is_fixable())
+                    && ui.button("Fix all").clicked()
+                {
+                    fix_all = true;
+                }
+            });
+            ui.separator();
+
+            egui::ScrollArea::vertical().show(ui, |ui| {
+                for (index, diagnostic) in diagnostics.iter().enumerate() {
+                    ui.horizontal(|ui| {
+                        let (label, color) = match diagnostic.severity {
+                            Severity::Error => ("Error", egui::Color32::from_rgb(198, 40, 40)),
+                            Severity::Warning => ("Warning", egui::Color32::from_rgb(230, 160, 0)),
+                        };
+                        ui.colored_label(color, label);
+                        let selected = state.focused_tile == Some((diagnostic.x, diagnostic.y));
+                        let text = format!(
+                            "({}, {}) {}",
+                            diagnostic.x,
+                            diagnostic.y,
+                            diagnostic.message()
+                        );
+                        if ui.selectable_label(selected, text).clicked() {
+                            focus = Some((diagnostic.x, diagnostic.y));
+                        }
+                        if diagnostic.is_fixable() && ui.small_button("Fix").clicked() {
+                            fix = Some(index);
+                        }
+                    });
+                }
+            });
+        });
+
+    if !open {
+        state.show_validation = false;
+        state.focused_tile = None;
+    }
+
+    if let Some(tile) = focus {
+        state.focused_tile = Some(tile);
+        state.focus_request = Some(tile);
+    }
+
+    let state = &mut *state;
+    let mut changed = false;
+    if fix_all {
+        changed = validation::apply_all_fixes(&mut state.map) > 0;
+    } else if let Some(diagnostic) = fix.and_then(|index| {
+        state
+            .diagnostics
+            .as_ref()
+            .and_then(|diagnostics| diagnostics.get(index))
+    }) {
+        changed = validation::apply_fix(&mut state.map, diagnostic);
+    }
+    if changed {
+        state.mark_map_dirty();
+        state.map_modified = true;
+    }
+}
+
+fn recovery_prompt(
+    mut egui_ctx: EguiContexts,
+    mut state: ResMut<crate::editor::EditorState>,
+    mut autosave: ResMut<AutosaveState>,
+) {
+    let Some(candidate) = autosave.recovery.as_ref() else {
+        return;
+    };
+
+    let mut restore = false;
+    let mut discard = false;
+
+    egui::Window::new("Recover Unsaved Work")
+        .collapsible(false)
+        .resizable(false)
+        .anchor(egui::Align2::CENTER_CENTER, egui::Vec2::ZERO)
+        .show(egui_ctx.ctx_mut(), |ui| {
+            let age = candidate.saved_at.elapsed().unwrap_or_default().as_secs() / 60;
+            match candidate.source.as_ref() {
+                Some(source) => ui.label(format!(
+                    "An autosave of {} from {age} minute(s) ago is newer than the saved map.",
+                    source.display()
+                )),
+                None => ui.label(format!(
+                    "An autosave of an unsaved map from {age} minute(s) ago was found."
+                )),
+            };
+            ui.label(format!("Backups are kept in {}/.", autosave::RECOVERY_DIR));
+            ui.horizontal(|ui| {
+                restore = ui.button("Restore").clicked();
+                discard = ui.button("Discard").clicked();
+            });
+        });
+
+    if restore {
+        match load_map(&candidate.backup_path) {
+            Ok(map) => {
+                state.replace_map(map);
+                state.map_modified = true;
+                state.current_file_path = candidate.source.clone();
+                state.last_export_status = Some(ExportStatus::Success(
+                    "Restored map from autosave".to_string(),
+                ));
+            }
+            Err(err) => {
+                eprintln!("Failed to restore autosave: {err:?}");
+                state.last_export_status =
+                    Some(ExportStatus::Failure(format!("Restore failed: {err:#}")));
+            }
+        }
+        autosave.recovery = None;
+    } else if discard {
+        autosave::dismiss_recovery(candidate);
+        autosave.recovery = None;
+    }
+}
+
+fn new_map_dialog(mut egui_ctx: EguiContexts, mut state: ResMut<EditorState>) {
+    let Some(mut settings) = state.new_map_dialog else {
+        return;
+    };
+
+    let mut open = true;
+    let mut create = false;
+    let mut cancel = false;
+
+    egui::Window::new("New Map")
+        .open(&mut open)
+        .collapsible(false)
+        .resizable(false)
+        .show(egui_ctx.ctx_mut(), |ui| {
+            egui::Grid::new("new_map_grid")
+                .num_columns(2)
+                .spacing([12.0, 6.0])
+                .show(ui, |ui| {
+                    ui.label("Width:");
+                    ui.add(
+                        egui::DragValue::new(&mut settings.width).range(1..=generate::MAX_MAP_SIZE),
+                    );
+                    ui.end_row();
+
+                    ui.label("Height:");
+                    ui.add(
+                        egui::DragValue::new(&mut settings.height)
+                            .range(1..=generate::MAX_MAP_SIZE),
+                    );
+                    ui.end_row();
+
+                    ui.label("Default tile:");
+                    egui::ComboBox::from_id_source("new_map_tile_type")
+                        .selected_text(format!("{:?}", settings.tile_type))
+                        .show_ui(ui, |ui| {
+                            for tile_type in TileType::ALL {
+                                ui.selectable_value(
+                                    &mut settings.tile_type,
+                                    tile_type,
+                                    format!("{tile_type:?}"),
+                                );
+                            }
+                        });
+                    ui.end_row();
+
+                    ui.label("Elevation:");
+                    ui.horizontal(|ui| {
+                        for e in 0..=3 {
+                            ui.selectable_value(&mut settings.elevation, e, format!("{e}"));
+                        }
+                    });
+                    ui.end_row();
+
+                    ui.label("Template:");
+                    ui.horizontal(|ui| {
+                        let is_blank = settings.template == MapTemplate::Blank;
+                        if ui.selectable_label(is_blank, "Blank").clicked() {
+                            settings.template = MapTemplate::Blank;
+                        }
+                        if ui.selectable_label(!is_blank, "Random hills").clicked() && is_blank {
+                            settings.template = MapTemplate::RandomHills {
+                                seed: random_seed(),
+                            };
+                        }
+                    });
+                    ui.end_row();
+
+                    if let MapTemplate::RandomHills { seed } = &mut settings.template {
+                        ui.label("Seed:");
+                        ui.horizontal(|ui| {
+                            ui.add(egui::DragValue::new(seed));
+                            if ui.small_button("Randomize").clicked() {
+                                *seed = random_seed();
+                            }
+                        });
+                        ui.end_row();
+                    }
+                });
+
+            ui.separator();
+            ui.horizontal(|ui| {
+                create = ui.button("Create").clicked();
+                cancel = ui.button("Cancel").clicked();
+            });
+        });
+
+    if !open || cancel {
+        state.new_map_dialog = None;
+    } else if create {
+        state.new_map_dialog = None;
+        request_action(&mut state, PendingAction::New(settings));
+    } else {
+        state.new_map_dialog = Some(settings);
+    }
+}
+
+fn heightmap_dialog(mut egui_ctx: EguiContexts, mut state: ResMut<EditorState>) {
+    let state = &mut *state;
+    let Some(dialog) = state.heightmap_dialog.as_mut() else {
+        return;
+    };
+
+    if let Some((_, task)) = dialog.pick_task.as_mut()
+        && task.is_finished()
+    {
+        let (slot, task) = dialog.pick_task.take().unwrap();
+        if let Some(path) = block_on(task) {
+            match slot {
+                HeightmapImage::Heights => dialog.heightmap = Some(path),
+                HeightmapImage::TileTypes => dialog.tile_types = Some(path),
+            }
+        }
+    }
+
+    let mut open = true;
+    let mut pick = None;
+    let mut import = false;
+
+    egui::Window::new("Import Heightmap")
+        .open(&mut open)
+        .default_width(360.0)
+        .show(egui_ctx.ctx_mut(), |ui| {
+            let picking = dialog.pick_task.is_some();
+            ui.horizontal(|ui| {
+                ui.label("Heightmap:");
+                ui.label(file_label(dialog.heightmap.as_deref()));
+                if ui
+                    .add_enabled(!picking, egui::Button::new("Browse…"))
+                    .clicked()
+                {
+                    pick = Some(HeightmapImage::Heights);
+                }
+            });
+            ui.horizontal(|ui| {
+                ui.label("Tile types (optional):");
+                ui.label(file_label(dialog.tile_types.as_deref()));
+                if ui
+                    .add_enabled(!picking, egui::Button::new("Browse…"))
+                    .clicked()
+                {
+                    pick = Some(HeightmapImage::TileTypes);
+                }
+                if dialog.tile_types.is_some() && ui.small_button("Clear").clicked() {
+                    dialog.tile_types = None;
+                }
+            });
+
+            ui.separator();
+            let settings = &mut dialog.settings;
+            ui.horizontal(|ui| {
+                ui.label("Base elevation:");
+                for e in 0..=3 {
+                    ui.selectable_value(&mut settings.base_elevation, e, format!("{e}"));
+                }
+            });
+            ui.label("Brightness at which each step up begins:");
+            let mut remove = None;
+            for (index, threshold) in settings.thresholds.iter_mut().enumerate() {
+                ui.horizontal(|ui| {
+                    ui.label(format!("Step {}:", index + 1));
+                    ui.add(
+                        egui::DragValue::new(threshold)
+                            .range(0.0..=1.0)
+                            .speed(0.005)
+                            .fixed_decimals(3),
+                    );
+                    if ui.small_button("Remove").clicked() {
+                        remove = Some(index);
                     }
+                });
+            }
+            if let Some(index) = remove {
+                settings.thresholds.remove(index);
+            }
+            let room = (generate::MAX_ELEVATION - settings.base_elevation).max(0) as usize;
+            if ui
+                .add_enabled(
+                    settings.thresholds.len() < room,
+                    egui::Button::new("Add step").small(),
+                )
+                .clicked()
+            {
+                let last = settings.thresholds.last().copied().unwrap_or(0.0);
+                settings.thresholds.push((last + 0.1).min(1.0));
+            }
+
+            ui.separator();
+            ui.checkbox(&mut settings.auto_ramps, "Place ramps on gentle slopes");
+            if settings.auto_ramps {
+                ui.horizontal(|ui| {
+                    ui.label("Max slope drop:");
+                    ui.add(
+                        egui::DragValue::new(&mut settings.ramp_max_drop)
+                            .range(0.0..=1.0)
+                            .speed(0.005)
+                            .fixed_decimals(3),
+                    );
+                });
+            }
+
+            if dialog.tile_types.is_some() {
+                ui.separator();
+                ui.label("Tile type colours:");
+                for entry in settings.palette.iter_mut() {
+                    ui.horizontal(|ui| {
+                        ui.color_edit_button_srgb(&mut entry.color);
+                        ui.label(format!("{:?}", entry.tile_type));
+                    });
+                }
+            }
+
+            ui.separator();
+            import = ui
+                .add_enabled(dialog.heightmap.is_some(), egui::Button::new("Import"))
+                .clicked();
+        });
+
+    if let Some(slot) = pick {
+        let title = match slot {
+            HeightmapImage::Heights => "Open Heightmap",
+            HeightmapImage::TileTypes => "Open Tile Type Image",
+        };
+        let file_dialog = AsyncFileDialog::new()
+            .set_title(title)
+            .add_filter("PNG Image", &["png"]);
+        dialog.pick_task = Some((
+            slot,
+            IoTaskPool::get().spawn(async move {
+                file_dialog
+                    .pick_file()
+                    .await
+                    .map(|file| file.path().to_path_buf())
+            }),
+        ));
+    }
+
+    if import && let Some(path) = dialog.heightmap.clone() {
+        let mut map = state.map.clone();
+        match heightmap::import_heightmap(
+            &mut map,
+            &path,
+            dialog.tile_types.as_deref(),
+            &dialog.settings,
+        ) {
+            Ok(()) => {
+                state.replace_map(map);
+                state.map_modified = true;
+                state.heightmap_dialog = None;
+                state.last_export_status = Some(ExportStatus::Success(format!(
+                    "Imported heightmap {}",
+                    path.display()
+                )));
+                return;
+            }
+            Err(err) => {
+                eprintln!("Failed to import heightmap: {err:?}");
+                state.last_export_status = Some(ExportStatus::Failure(format!(
+                    "Heightmap import failed: {err:#}"
+                )));
+            }
+        }
+    }
+
+    if !open {
+        state.heightmap_dialog = None;
+    }
+}
+
+fn file_label(path: Option<&Path>) -> String {
+    path.and_then(|path| path.file_name())
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_else(|| "(none)".to_string())
+}
+
+fn random_seed() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_nanos() as u64)
+        .unwrap_or_default()
+}
+
+fn unsaved_changes_prompt(mut egui_ctx: EguiContexts, mut state: ResMut<EditorState>) {
+    let Some(action) = state.unsaved_prompt else {
+        return;
+    };
+
+    let mut save = false;
+    let mut discard = false;
+    let mut cancel = false;
+
+    egui::Window::new("Unsaved Changes")
+        .collapsible(false)
+        .resizable(false)
+        .anchor(egui::Align2::CENTER_CENTER, egui::Vec2::ZERO)
+        .show(egui_ctx.ctx_mut(), |ui| {
+            let verb = match action {
+                PendingAction::Load => "loading another map",
+                PendingAction::OpenPackage => "opening a package",
+                PendingAction::ImportTiled => "importing a Tiled map",
+                PendingAction::New(_) => "starting a new map",
+                PendingAction::Close => "closing",
+            };
+            ui.label(format!(
+                "The map has unsaved changes. Save them before {verb}?"
+            ));
+            ui.horizontal(|ui| {
+                save = ui.button("Save").clicked();
+                discard = ui.button("Discard").clicked();
+                cancel = ui.button("Cancel").clicked();
+            });
+        });
+
+    if save {
+        state.unsaved_prompt = None;
+        match state.current_file_path.clone() {
+            Some(path) => match save_map(&path, &state.map) {
+                Ok(()) => {
+                    state.map_modified = false;
+                    run_action(&mut state, action);
+                }
+                Err(err) => {
+                    eprintln!("Failed to save map: {err:?}");
+                    state.last_export_status =
+                        Some(ExportStatus::Failure(format!("Save failed: {err:#}")));
+                }
+            },
+            None => {
+                state.after_save = Some(action);
+                if state.save_dialog_task.is_none() {
+                    open_save_dialog(&mut state);
                 }
             }
         }
+    } else if discard {
+        state.unsaved_prompt = None;
+        run_action(&mut state, action);
+    } else if cancel {
+        state.unsaved_prompt = None;
     }
 }
 
+fn handle_close_requests(
+    mut requests: EventReader<WindowCloseRequested>,
+    mut state: ResMut<EditorState>,
+    mut exit: EventWriter<AppExit>,
+) {
+    if requests.read().last().is_some() {
+        request_action(&mut state, PendingAction::Close);
+    }
+    if state.close_confirmed {
+        exit.send(AppExit::Success);
+    }
+}
+
+fn update_window_title(
+    state: Res<EditorState>,
+    mut windows: Query<&mut Window, With<PrimaryWindow>>,
+) {
+    if !state.is_changed() {
+        return;
+    }
+    let Ok(mut window) = windows.get_single_mut() else {
+        return;
+    };
+
+    let name = state
+        .current_file_path
+        .as_ref()
+        .and_then(|path| path.file_name())
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_else(|| "Untitled".to_string());
+    let marker = if state.map_modified { "*" } else { "" };
+    let title = format!("{name}{marker} - Tilemap Edit 3D");
+    if window.title != title {
+        window.title = title;
+    }
+}
+
+/// Runs `action` straight away, or asks first if it would throw away unsaved edits.
+fn request_action(state: &mut EditorState, action: PendingAction) {
+    if state.map_modified {
+        state.unsaved_prompt = Some(action);
+    } else {
+        run_action(state, action);
+    }
+}
+
+fn run_action(state: &mut EditorState, action: PendingAction) {
+    match action {
+        PendingAction::Load => open_load_dialog(state),
+        PendingAction::OpenPackage => open_package_dialog(state),
+        PendingAction::ImportTiled => open_tiled_import_dialog(state),
+        PendingAction::New(settings) => state.start_new_map(generate::generate_map(&settings)),
+        PendingAction::Close => state.close_confirmed = true,
+    }
+}
+
+fn open_save_dialog(state: &mut EditorState) {
+    let mut dialog = AsyncFileDialog::new()
+        .set_title("Save Map")
+        .add_filter("Binary Map", &["bin"])
+        .add_filter("JSON Map", &["json"])
+        .add_filter("RON Map", &["ron"]);
+    if let Some(path) = state.current_file_path.as_ref() {
+        if let Some(parent) = path.parent() {
+            dialog = dialog.set_directory(parent);
+        }
+        if let Some(file_name) = path.file_name().and_then(|name| name.to_str()) {
+            dialog = dialog.set_file_name(file_name);
+        }
+    }
+
+    state.save_dialog_task = Some(IoTaskPool::get().spawn(async move {
+        dialog
+            .save_file()
+            .await
+            .map(|file| file.path().to_path_buf())
+    }));
+}
+
+fn open_load_dialog(state: &mut EditorState) {
+    let mut dialog = AsyncFileDialog::new()
+        .set_title("Open Map")
+        .add_filter("Tile Map", &["bin", "json", "ron"])
+        .add_filter("All Files", &["*"]);
+    if let Some(parent) = state.current_file_path.as_ref().and_then(|p| p.parent()) {
+        dialog = dialog.set_directory(parent);
+    }
+
+    state.load_dialog_task = Some(IoTaskPool::get().spawn(async move {
+        dialog
+            .pick_file()
+            .await
+            .map(|file| file.path().to_path_buf())
+    }));
+}
+
+fn open_package_dialog(state: &mut EditorState) {
+    let mut dialog = AsyncFileDialog::new()
+        .set_title("Open Map Package")
+        .add_filter("Tile Map Package", &["tmemapdata"]);
+    if let Some(parent) = state.current_file_path.as_ref().and_then(|p| p.parent()) {
+        dialog = dialog.set_directory(parent);
+    }
+
+    state.package_dialog_task = Some(IoTaskPool::get().spawn(async move {
+        dialog
+            .pick_file()
+            .await
+            .map(|file| file.path().to_path_buf())
+    }));
+}
+
+fn open_tiled_import_dialog(state: &mut EditorState) {
+    let mut dialog = AsyncFileDialog::new()
+        .set_title("Import Tiled Map")
+        .add_filter("Tiled JSON Map", &["tmj", "json"]);
+    if let Some(parent) = state.current_file_path.as_ref().and_then(|p| p.parent()) {
+        dialog = dialog.set_directory(parent);
+    }
+
+    state.tiled_import_task = Some(IoTaskPool::get().spawn(async move {
+        dialog
+            .pick_file()
+            .await
+            .map(|file| file.path().to_path_buf())
+    }));
+}
+
+fn open_tiled_export_dialog(state: &mut EditorState) {
+    let mut dialog = AsyncFileDialog::new()
+        .set_title("Export Tiled Map")
+        .add_filter("Tiled JSON Map", &["tmj", "json"]);
+    if let Some(path) = state.current_file_path.as_ref() {
+        if let Some(parent) = path.parent() {
+            dialog = dialog.set_directory(parent);
+        }
+        if let Some(stem) = path.file_stem().and_then(|name| name.to_str()) {
+            dialog = dialog.set_file_name(format!("{stem}.tmj"));
+        }
+    } else {
+        dialog = dialog.set_file_name("map.tmj");
+    }
+
+    state.tiled_export_task = Some(IoTaskPool::get().spawn(async move {
+        dialog
+            .save_file()
+            .await
+            .map(|file| file.path().to_path_buf())
+    }));
+}
+
 struct PaletteItem {
     tile_type: TileType,
     name: String,
@@ -0,0 +1,396 @@
+use std::collections::VecDeque;
+
+use crate::pathing::{self, WalkGrid};
+use crate::terrain;
+use crate::types::{TILE_HEIGHT, TileKind, TileMap};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Severity {
+    Warning,
+    Error,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum DiagnosticKind {
+    /// `TileMap::tiles` does not hold exactly `width * height` tiles.
+    TileCountMismatch { expected: usize, actual: usize },
+    /// A ramp has no lower cardinal neighbour, so it renders as a flat tile.
+    RampWithoutTarget,
+    /// A ramp has no stored direction and relies on `find_ramp_target`.
+    RampMissingDirection,
+    /// The stored ramp direction no longer points to a lower neighbour.
+    RampDirectionInvalid,
+    /// A walkable region above the lowest level that no ramp leads into.
+    UnreachablePlateau { elevation: i8, size: usize },
+    /// `Tile::x`/`Tile::y` disagree with the tile's position in `TileMap::tiles`.
+    TileCoordinateMismatch { stored: (u32, u32) },
+    /// A walkability override refers to a cell outside the map.
+    WalkOverrideOutOfBounds,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    pub kind: DiagnosticKind,
+    pub x: u32,
+    pub y: u32,
+}
+
+impl Diagnostic {
+    pub fn message(&self) -> String {
+        match &self.kind {
+            DiagnosticKind::TileCountMismatch { expected, actual } => {
+                format!("Map holds {actual} tiles but its size requires {expected}")
+            }
+            DiagnosticKind::RampWithoutTarget => {
+                "Ramp has no lower neighbour and renders flat".to_string()
+            }
+            DiagnosticKind::RampMissingDirection => {
+                "Ramp has no direction; falling back to the lowest neighbour".to_string()
+            }
+            DiagnosticKind::RampDirectionInvalid => {
+                "Ramp direction does not point to a lower neighbour".to_string()
+            }
+            DiagnosticKind::UnreachablePlateau { elevation, size } => {
+                format!(
+                    "Plateau at elevation {elevation} ({size} tiles) is not reachable by any ramp"
+                )
+            }
+            DiagnosticKind::TileCoordinateMismatch { stored } => format!(
+                "Tile stores coordinates ({}, {}) but sits at ({}, {})",
+                stored.0, stored.1, self.x, self.y
+            ),
+            DiagnosticKind::WalkOverrideOutOfBounds => {
+                "Walkability override lies outside the map".to_string()
+            }
+        }
+    }
+
+    pub fn is_fixable(&self) -> bool {
+        !matches!(
+            self.kind,
+            DiagnosticKind::UnreachablePlateau { .. } | DiagnosticKind::TileCountMismatch { .. }
+        )
+    }
+}
+
+/// Runs every check against the map. Diagnostics are sorted by severity (errors
+/// first) and then by position.
+pub fn validate_map(map: &TileMap) -> Vec<Diagnostic> {
+    let mut diagnostics = Vec::new();
+
+    let expected = (map.width * map.height) as usize;
+    if map.tiles.len() != expected {
+        // Every other check indexes into `tiles`; bail out before they panic.
+        diagnostics.push(Diagnostic {
+            severity: Severity::Error,
+            kind: DiagnosticKind::TileCountMismatch {
+                expected,
+                actual: map.tiles.len(),
+            },
+            x: 0,
+            y: 0,
+        });
+        return diagnostics;
+    }
+
+    check_tile_coordinates(map, &mut diagnostics);
+    check_ramps(map, &mut diagnostics);
+    check_walk_overrides(map, &mut diagnostics);
+    check_unreachable_plateaus(map, &pathing::build_walk_grid(map), &mut diagnostics);
+
+    diagnostics.sort_by(|a, b| {
+        b.severity
+            .cmp(&a.severity)
+            .then_with(|| (a.y, a.x).cmp(&(b.y, b.x)))
+    });
+    diagnostics
+}
+
+fn check_tile_coordinates(map: &TileMap, diagnostics: &mut Vec<Diagnostic>) {
+    for y in 0..map.height {
+        for x in 0..map.width {
+            let tile = map.get(x, y);
+            if tile.x != x || tile.y != y {
+                diagnostics.push(Diagnostic {
+                    severity: Severity::Warning,
+                    kind: DiagnosticKind::TileCoordinateMismatch {
+                        stored: (tile.x, tile.y),
+                    },
+                    x,
+                    y,
+                });
+            }
+        }
+    }
+}
+
+fn check_ramps(map: &TileMap, diagnostics: &mut Vec<Diagnostic>) {
+    for y in 0..map.height {
+        for x in 0..map.width {
+            let tile = map.get(x, y);
+            if tile.kind != TileKind::Ramp {
+                continue;
+            }
+
+            let base = tile.elevation as f32 * TILE_HEIGHT;
+            let kind = if terrain::find_ramp_target(map, x, y, base).is_none() {
+                Some(DiagnosticKind::RampWithoutTarget)
+            } else {
+                match tile.ramp_direction {
+                    None => Some(DiagnosticKind::RampMissingDirection),
+                    Some(dir) if terrain::ramp_neighbor_height(map, x, y, dir, base).is_none() => {
+                        Some(DiagnosticKind::RampDirectionInvalid)
+                    }
+                    Some(_) => None,
+                }
+            };
+
+            if let Some(kind) = kind {
+                let severity = match kind {
+                    DiagnosticKind::RampMissingDirection => Severity::Warning,
+                    _ => Severity::Error,
+                };
+                diagnostics.push(Diagnostic {
+                    severity,
+                    kind,
+                    x,
+                    y,
+                });
+            }
+        }
+    }
+}
+
+fn check_walk_overrides(map: &TileMap, diagnostics: &mut Vec<Diagnostic>) {
+    for cell in &map.walk_overrides {
+        if cell.x >= map.width || cell.y >= map.height {
+            diagnostics.push(Diagnostic {
+                severity: Severity::Warning,
+                kind: DiagnosticKind::WalkOverrideOutOfBounds,
+                x: cell.x,
+                y: cell.y,
+            });
+        }
+    }
+}
+
+fn check_unreachable_plateaus(map: &TileMap, grid: &WalkGrid, diagnostics: &mut Vec<Diagnostic>) {
+    let Some(lowest) = map.tiles.iter().map(|tile| tile.elevation).min() else {
+        return;
+    };
+
+    let mut visited = vec![false; map.tiles.len()];
+    let mut queue = VecDeque::new();
+    for y in 0..map.height {
+        for x in 0..map.width {
+            let start = map.idx(x, y);
+            if visited[start] || !grid.is_walkable(x, y) {
+                continue;
+            }
+
+            // Flood the connected region, noting whether a ramp or the lowest level touches it.
+            let mut size = 0;
+            let mut has_ramp = false;
+            let mut min_elevation = i8::MAX;
+            visited[start] = true;
+            queue.push_back((x, y));
+            while let Some((cx, cy)) = queue.pop_front() {
+                size += 1;
+                let tile = map.get(cx, cy);
+                has_ramp |= tile.kind == TileKind::Ramp;
+                min_elevation = min_elevation.min(tile.elevation);
+                for (_, nx, ny) in grid.neighbors(cx, cy) {
+                    let next = map.idx(nx, ny);
+                    if !visited[next] {
+                        visited[next] = true;
+                        queue.push_back((nx, ny));
+                    }
+                }
+            }
+
+            if !has_ramp && min_elevation > lowest {
+                diagnostics.push(Diagnostic {
+                    severity: Severity::Warning,
+                    kind: DiagnosticKind::UnreachablePlateau {
+                        elevation: min_elevation,
+                        size,
+                    },
+                    x,
+                    y,
+                });
+            }
+        }
+    }
+}
+
+/// Applies the automatic fix for a diagnostic. Returns `true` if the map changed.
+pub fn apply_fix(map: &mut TileMap, diagnostic: &Diagnostic) -> bool {
+    let (x, y) = (diagnostic.x, diagnostic.y);
+    match diagnostic.kind {
+        DiagnosticKind::TileCoordinateMismatch { .. } => {
+            if x >= map.width || y >= map.height {
+                return false;
+            }
+            let index = map.idx(x, y);
+            let tile = &mut map.tiles[index];
+            tile.x = x;
+            tile.y = y;
+            true
+        }
+        DiagnosticKind::RampWithoutTarget => {
+            if x >= map.width || y >= map.height {
+                return false;
+            }
+            let index = map.idx(x, y);
+            let tile = &mut map.tiles[index];
+            tile.kind = TileKind::Floor;
+            tile.ramp_direction = None;
+            true
+        }
+        DiagnosticKind::RampMissingDirection | DiagnosticKind::RampDirectionInvalid => {
+            if x >= map.width || y >= map.height {
+                return false;
+            }
+            // Pin the direction the renderer already falls back to, so the fix is invisible.
+            let base = map.get(x, y).elevation as f32 * TILE_HEIGHT;
+            let Some((dir, _)) = terrain::find_ramp_target(map, x, y, base) else {
+                return false;
+            };
+            let index = map.idx(x, y);
+            map.tiles[index].ramp_direction = Some(dir);
+            true
+        }
+        DiagnosticKind::WalkOverrideOutOfBounds => map.set_walk_override(x, y, None),
+        DiagnosticKind::UnreachablePlateau { .. } | DiagnosticKind::TileCountMismatch { .. } => {
+            false
+        }
+    }
+}
+
+/// Applies every available automatic fix. Returns the number of fixes applied.
+pub fn apply_all_fixes(map: &mut TileMap) -> usize {
+    let diagnostics = validate_map(map);
+    diagnostics
+        .iter()
+        .filter(|diagnostic| apply_fix(map, diagnostic))
+        .count()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::{RampDirection, WalkOverride, WalkOverrideCell};
+
+    fn set_tile(map: &mut TileMap, x: u32, y: u32, elevation: i8, kind: TileKind) {
+        let index = map.idx(x, y);
+        map.tiles[index].elevation = elevation;
+        map.tiles[index].kind = kind;
+    }
+
+    /// A flat 5x5 map with one instance of every per-tile problem.
+    fn broken_map() -> TileMap {
+        let mut map = TileMap::new(5, 5);
+        // A ramp on the lowest level has nothing to descend to.
+        set_tile(&mut map, 0, 4, 0, TileKind::Ramp);
+        // A raised ramp without a stored direction.
+        set_tile(&mut map, 1, 1, 1, TileKind::Ramp);
+        // A raised ramp pointing off the map.
+        set_tile(&mut map, 3, 0, 1, TileKind::Ramp);
+        let index = map.idx(3, 0);
+        map.tiles[index].ramp_direction = Some(RampDirection::North);
+        // A pillar no ramp leads onto.
+        set_tile(&mut map, 1, 3, 2, TileKind::Floor);
+        // A tile that stores the wrong column, and an override beyond the east edge.
+        let index = map.idx(4, 4);
+        map.tiles[index].x = 9;
+        map.walk_overrides.push(WalkOverrideCell {
+            x: 7,
+            y: 0,
+            mode: WalkOverride::Blocked,
+        });
+        map
+    }
+
+    #[test]
+    fn every_problem_is_reported_at_its_tile() {
+        let diagnostics = validate_map(&broken_map());
+        let expected = [
+            (DiagnosticKind::RampWithoutTarget, 0, 4),
+            (DiagnosticKind::RampMissingDirection, 1, 1),
+            (DiagnosticKind::RampDirectionInvalid, 3, 0),
+            (
+                DiagnosticKind::UnreachablePlateau {
+                    elevation: 2,
+                    size: 1,
+                },
+                1,
+                3,
+            ),
+            (
+                DiagnosticKind::TileCoordinateMismatch { stored: (9, 4) },
+                4,
+                4,
+            ),
+            (DiagnosticKind::WalkOverrideOutOfBounds, 7, 0),
+        ];
+        assert_eq!(diagnostics.len(), expected.len(), "{diagnostics:?}");
+        for (kind, x, y) in expected {
+            assert!(
+                diagnostics
+                    .iter()
+                    .any(|diagnostic| diagnostic.kind == kind
+                        && (diagnostic.x, diagnostic.y) == (x, y)),
+                "missing {kind:?} at ({x}, {y}) in {diagnostics:?}"
+            );
+        }
+        // Errors come first.
+        assert!(
+            diagnostics
+                .windows(2)
+                .all(|pair| pair[0].severity >= pair[1].severity)
+        );
+    }
+
+    #[test]
+    fn fixes_leave_only_unfixable_diagnostics() {
+        let mut map = broken_map();
+        assert_eq!(apply_all_fixes(&mut map), 5);
+
+        assert_eq!(map.get(0, 4).kind, TileKind::Floor);
+        assert!(map.get(1, 1).ramp_direction.is_some());
+        assert_ne!(map.get(3, 0).ramp_direction, Some(RampDirection::North));
+        assert_eq!(map.get(4, 4).x, 4);
+        assert!(map.walk_overrides.is_empty());
+
+        let remaining = validate_map(&map);
+        assert!(remaining.iter().all(|diagnostic| !diagnostic.is_fixable()));
+        assert_eq!(remaining.len(), 1);
+        assert!(matches!(
+            remaining[0].kind,
+            DiagnosticKind::UnreachablePlateau { .. }
+        ));
+        assert!(!apply_fix(&mut map, &remaining[0]));
+    }
+
+    #[test]
+    fn tile_count_mismatch_stops_validation() {
+        let mut map = broken_map();
+        map.tiles.pop();
+        let diagnostics = validate_map(&map);
+        assert_eq!(
+            diagnostics,
+            vec![Diagnostic {
+                severity: Severity::Error,
+                kind: DiagnosticKind::TileCountMismatch {
+                    expected: 25,
+                    actual: 24,
+                },
+                x: 0,
+                y: 0,
+            }]
+        );
+        assert!(!diagnostics[0].is_fixable());
+        assert_eq!(apply_all_fixes(&mut map), 0);
+    }
+}
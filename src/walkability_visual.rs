@@ -0,0 +1,97 @@
+use bevy::prelude::*;
+
+use crate::editor::EditorState;
+use crate::pathing::{self, Neighbor, WalkGrid};
+use crate::terrain::{self, TerrainMeshSet};
+use crate::types::TILE_SIZE;
+
+const CONNECTION_COLOR: Color = Color::srgb(0.2, 0.8, 1.0);
+const BLOCKED_COLOR: Color = Color::srgb(0.9, 0.2, 0.2);
+const OVERLAY_OFFSET: f32 = 0.05;
+
+pub struct WalkabilityVisualPlugin;
+
+impl Plugin for WalkabilityVisualPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<WalkabilityOverlay>()
+            .add_systems(
+                Update,
+                rebuild_walkability_overlay.in_set(TerrainMeshSet::Rebuild),
+            )
+            .add_systems(Update, draw_walkability_overlay);
+    }
+}
+
+#[derive(Resource, Default)]
+pub struct WalkabilityOverlay {
+    pub grid: Option<WalkGrid>,
+}
+
+fn rebuild_walkability_overlay(state: Res<EditorState>, mut overlay: ResMut<WalkabilityOverlay>) {
+    if !state.show_walkability {
+        overlay.grid = None;
+        return;
+    }
+
+    if state.map_dirty || overlay.grid.is_none() {
+        overlay.grid = Some(pathing::build_walk_grid(&state.map));
+    }
+}
+
+fn draw_walkability_overlay(
+    mut gizmos: Gizmos,
+    state: Res<EditorState>,
+    overlay: Res<WalkabilityOverlay>,
+) {
+    if !state.show_walkability {
+        return;
+    }
+    let Some(grid) = overlay.grid.as_ref() else {
+        return;
+    };
+    if grid.width != state.map.width || grid.height != state.map.height {
+        return;
+    }
+
+    for y in 0..grid.height {
+        for x in 0..grid.width {
+            let center = cell_center(&state, x, y);
+
+            if !grid.is_walkable(x, y) {
+                let half = TILE_SIZE * 0.3;
+                gizmos.line(
+                    center + Vec3::new(-half, 0.0, -half),
+                    center + Vec3::new(half, 0.0, half),
+                    BLOCKED_COLOR,
+                );
+                gizmos.line(
+                    center + Vec3::new(half, 0.0, -half),
+                    center + Vec3::new(-half, 0.0, half),
+                    BLOCKED_COLOR,
+                );
+                continue;
+            }
+
+            // Each link is drawn once, from the cell that owns the east/south side.
+            for (neighbor, nx, ny) in grid.neighbors(x, y) {
+                if !matches!(
+                    neighbor,
+                    Neighbor::East | Neighbor::SouthEast | Neighbor::South | Neighbor::SouthWest
+                ) {
+                    continue;
+                }
+                gizmos.line(center, cell_center(&state, nx, ny), CONNECTION_COLOR);
+            }
+        }
+    }
+}
+
+fn cell_center(state: &EditorState, x: u32, y: u32) -> Vec3 {
+    let heights = terrain::tile_corner_heights(&state.map, x, y);
+    let height = heights.iter().sum::<f32>() / heights.len() as f32;
+    Vec3::new(
+        (x as f32 + 0.5) * TILE_SIZE,
+        height + OVERLAY_OFFSET,
+        (y as f32 + 0.5) * TILE_SIZE,
+    )
+}